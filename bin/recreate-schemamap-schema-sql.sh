@@ -14,3 +14,8 @@ for file in "$DEVENV_ROOT/sql/"*; do
     cat "$file"
   } >> "$DEVENV_ROOT/rust/create_schemamap_schema.sql"
 done
+
+# `schemamap init/upgrade` embed the migrations one by one, the crate can't reach outside of rust/
+rm -f "$DEVENV_ROOT/rust/sql/"*.sql
+mkdir -p "$DEVENV_ROOT/rust/sql"
+cp "$DEVENV_ROOT/sql/"*.sql "$DEVENV_ROOT/rust/sql/"
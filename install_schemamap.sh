@@ -1,7 +1,7 @@
 #!/usr/bin/env bash
 
 echo 'Downloading Schemamap.io migration files...'
-wget -qN schemamap.dev/create_schemamap_users.sql schemamap.dev/create_schemamap_schema.sql schemamap.dev/grant_schemamap_usage.sql
+wget -qN schemamap.dev/create_schemamap_users.sql schemamap.dev/create_schemamap_schema.sql schemamap.dev/grant_schemamap_usage.sql schemamap.dev/grant_schemamap_app_role.sql
 
 export PGDATABASE="${PGDATABASE:-postgres}"
 export PGHOST="${PGHOST:-localhost}"
@@ -20,18 +20,44 @@ if ! psql -c 'SELECT 1;' > /dev/null 2>&1; then
     exit 1
 fi
 
+generate_password() {
+    openssl rand -hex 24
+}
+
+SCHEMAMAP_PASSWORD="${SCHEMAMAP_PASSWORD:-$(generate_password)}"
+SCHEMAMAP_SCHEMA_READ_PASSWORD="${SCHEMAMAP_SCHEMA_READ_PASSWORD:-$(generate_password)}"
+SCHEMAMAP_READONLY_PASSWORD="${SCHEMAMAP_READONLY_PASSWORD:-$(generate_password)}"
+SCHEMAMAP_READWRITE_PASSWORD="${SCHEMAMAP_READWRITE_PASSWORD:-$(generate_password)}"
+
 echo
 echo "Creating Schemamap.io users in $PGDATABASE"
 echo "grant connect, create on database $PGDATABASE to schemamap;" >> ./create_schemamap_users.sql
 # NOTE: not running in a transaction (-1), so install script is idempotent in case of failures
-psql -f ./create_schemamap_users.sql
+psql -v schemamap_password="$SCHEMAMAP_PASSWORD" \
+     -v schemamap_schema_read_password="$SCHEMAMAP_SCHEMA_READ_PASSWORD" \
+     -v schemamap_readonly_password="$SCHEMAMAP_READONLY_PASSWORD" \
+     -v schemamap_readwrite_password="$SCHEMAMAP_READWRITE_PASSWORD" \
+     -f ./create_schemamap_users.sql
 
 echo
 echo "Installing 'schemamap' schema to $PGDATABASE"
 export PGUSER=schemamap
-export PGPASSWORD=schemamap
+export PGPASSWORD="$SCHEMAMAP_PASSWORD"
 psql -1 -f ./create_schemamap_schema.sql
 
 echo
-echo "Granting usage rights of schemamap to all roles"
+echo "Granting usage rights of schemamap to the schemamap roles"
 psql -1 -f ./grant_schemamap_usage.sql
+
+# Comma-separated, like for `schemamap init`
+for app_role in ${SCHEMAMAP_APP_ROLES//,/ }; do
+    echo "Granting usage rights of schemamap to $app_role"
+    psql -1 -v app_role="$app_role" -f ./grant_schemamap_app_role.sql
+done
+
+echo
+echo "Schemamap.io role passwords, store them in your secret manager:"
+echo "schemamap: $SCHEMAMAP_PASSWORD"
+echo "schemamap_schema_read: $SCHEMAMAP_SCHEMA_READ_PASSWORD"
+echo "schemamap_readonly: $SCHEMAMAP_READONLY_PASSWORD"
+echo "schemamap_readwrite: $SCHEMAMAP_READWRITE_PASSWORD"
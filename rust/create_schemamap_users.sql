@@ -6,15 +6,17 @@ begin
   end if;
 end $$;
 
--- Create the roles
--- TODO: change the passwords!
-create user schemamap with connection limit 5 encrypted password 'schemamap';
+-- Create the roles, with the passwords given as psql variables:
+-- psql -v schemamap_password=... -v schemamap_schema_read_password=... \
+--   -v schemamap_readonly_password=... -v schemamap_readwrite_password=... -f create_schemamap_users.sql
+-- `schemamap init` passes SCRAM-SHA-256 verifiers instead, so the server never sees the plain passwords.
+create user schemamap with connection limit 5 password :'schemamap_password';
 
 -- Extra roles, if you want to be extra safe/more granular with permissions
 -- otherwise, feel free to remove.
-create user schemamap_schema_read with connection limit 5 password 'schemamap_schema_read';
-create user schemamap_readonly with connection limit 5 password 'schemamap_readonly';
-create user schemamap_readwrite with connection limit 5 password 'schemamap_readwrite';
+create user schemamap_schema_read with connection limit 5 password :'schemamap_schema_read_password';
+create user schemamap_readonly with connection limit 5 password :'schemamap_readonly_password';
+create user schemamap_readwrite with connection limit 5 password :'schemamap_readwrite_password';
 
 -- create role capability hierarchy: least to most permissive
 grant schemamap_schema_read to schemamap_readonly;
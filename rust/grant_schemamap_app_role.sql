@@ -0,0 +1,23 @@
+-- Lets an application role use the SDK, run once per role after grant_schemamap_usage.sql:
+-- psql -v app_role=my_app -f grant_schemamap_app_role.sql
+
+-- the objects are owned by schemamap, so act as it to grant on them
+grant schemamap to CURRENT_USER;
+
+grant usage, create on schema schemamap to :"app_role";
+
+-- These changes allow your application roles & migrations to use the stored procedures, like:
+-- select * from schemamap.verify_installation();
+grant execute on all functions in schema schemamap to :"app_role";
+alter default privileges in schema schemamap grant execute on functions to :"app_role";
+
+-- These changes allow your application roles & migrations to check the status of data_migrations
+-- and their related tables: \dt schemamap.dm_*
+-- select * from schemamap.data_migrations;
+grant select on all tables in schema schemamap to :"app_role";
+alter default privileges in schema schemamap grant select on tables to :"app_role";
+
+-- allow all operations on data migration table, so import/dispose functions can UPDATE/DELETE records
+grant all on schemamap.data_migrations to :"app_role";
+
+revoke schemamap from CURRENT_USER;
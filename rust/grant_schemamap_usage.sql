@@ -48,22 +48,7 @@ begin
     end loop;
 end $$;
 
--- TODO: replace `PUBLIC` with your application DB role(s)
-grant usage, create on schema schemamap to PUBLIC;
-
--- These changes allow your application roles & migrations to use the stored procedures, like:
--- select * from schemamap.verify_installation();
-grant execute on all functions in schema schemamap to PUBLIC;
-alter default privileges in schema schemamap grant execute ON functions TO PUBLIC;
-
--- These changes allow your application roles & migrations to check the status of data_migrations
--- and their related tables: \dt schemamap.dm_*
--- select * from schemamap.data_migrations;
-grant select on all tables in schema schemamap to PUBLIC;
-alter default privileges in schema schemamap grant select on tables to PUBLIC;
-
--- allow all operations on data migration table, so import/dispose functions can UPDATE/DELETE records
-grant all on schemamap.data_migrations to PUBLIC;
+-- Application roles get access via grant_schemamap_app_role.sql, nothing is granted to PUBLIC
 
 -- this change allows schemamap to always execute functions, even if created by other roles
 alter default privileges in schema schemamap grant execute ON functions TO schemamap;
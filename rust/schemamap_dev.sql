@@ -36,7 +36,12 @@ create table if not exists snapshots (
   template_db_name text not null,
   git_branch text,
   git_rev text,
-  created_at timestamptz not null default now()
+  created_at timestamptz not null default now(),
+  message text,
+  tags text[] not null default '{}',
+  -- see `schemamap list`, to tell which snapshots can be restored without re-running migrations
+  schema_fingerprint text,
+  migration_version text
 );
 
 create or replace function disallow_and_kill_connections(db_name snapshots.db_name%type)
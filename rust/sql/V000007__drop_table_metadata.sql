@@ -0,0 +1 @@
+drop table if exists schemamap.table_metadata;
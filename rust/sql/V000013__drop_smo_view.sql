@@ -0,0 +1 @@
+drop view if exists schemamap.smo cascade;
@@ -1,6 +1,11 @@
 use clap::{Parser, Subcommand};
 
-use crate::{doctor, init, porcelain, up};
+use crate::{
+    config::{self, ProjectConfig},
+    connections, credentials, doctor, hooks, init, migrations,
+    parsers::{Source, SupabaseDb},
+    porcelain, uninstall, up,
+};
 
 #[derive(Parser)]
 #[command(name = "schemamap")]
@@ -64,6 +69,60 @@ pub struct Cli {
     )]
     pub port: Option<u16>,
 
+    #[arg(
+        long,
+        value_name = "VARIANT-OR-FILE",
+        help = "Use the `to:` database of a pgsync config, e.g. `dev` for .pgsync-dev.yml",
+        global = true
+    )]
+    pub pgsync_config: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SERVICE",
+        help = "Docker-compose service to connect to, when several Postgres services are defined",
+        global = true
+    )]
+    pub service: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DB",
+        value_enum,
+        help = "Which DB of a Supabase project to connect to [default: local]",
+        global = true
+    )]
+    pub(crate) supabase_db: Option<SupabaseDb>,
+
+    #[arg(
+        long,
+        help = "Don't read DATABASE_URL and framework settings from .env, .env.local and .env.development files",
+        global = true
+    )]
+    pub(crate) no_dotenv: bool,
+
+    #[arg(
+        long,
+        value_name = "SOURCE",
+        value_enum,
+        value_delimiter = ',',
+        help = "Only discover the connection from these sources, in order. Can also be provided via SCHEMAMAP_SOURCES environment variable.",
+        global = true
+    )]
+    pub(crate) source: Vec<Source>,
+
+    #[arg(
+        long,
+        value_name = "SCHEMA",
+        help = "Schema the SDK is installed in [default: schemamap]. Can also be provided via SCHEMAMAP_SCHEMA environment variable.",
+        global = true
+    )]
+    pub(crate) schema: Option<String>,
+
+    // Loaded from schemamap.toml after parsing the CLI arguments
+    #[arg(skip)]
+    pub(crate) project: ProjectConfig,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -72,21 +131,36 @@ pub struct Cli {
 pub enum Commands {
     #[command(about = "Initialize the SDK in the given Postgres DB, idempotently")]
     Init(init::InitArgs),
+    #[command(about = "Apply the pending SDK migrations to the given Postgres DB")]
+    Upgrade(migrations::UpgradeArgs),
+    #[command(about = "Remove the SDK from the given Postgres DB, including its roles")]
+    Uninstall(uninstall::UninstallArgs),
+    #[command(about = "Set new passwords for the SDK roles")]
+    RotatePasswords(credentials::RotatePasswordsArgs),
     #[command(about = "Create a secure P2P tunnel to Schemamap.io.")]
     Up(up::UpArgs),
     #[command(about = "Check if the SDK is configured correctly")]
     Doctor(doctor::DoctorArgs),
+    #[command(about = "List the probed connection sources and which one is used")]
+    Connections(connections::ConnectionsArgs),
+    #[command(about = "Inspect the project configuration")]
+    Config(config::ConfigArgs),
     #[command(about = "Get a high-level overview of the current DB state")]
     Status(porcelain::StatusArgs),
     #[command(about = "Refresh the SMO materialized view to reflect the current DB state")]
     Refresh(porcelain::RefreshArgs),
     // `schemamap_dev` DB section, snapshot/restore
-    #[command(about = "Snapshot the current DB to a new snapshot")]
+    #[command(
+        about = "Snapshot the current DB to a new snapshot, or drop and garbage collect snapshots"
+    )]
     Snapshot(porcelain::SnapshotArgs),
     #[command(about = "Restore the current DB from a snapshot, destorying the current state")]
     Restore(porcelain::RestoreArgs),
     #[command(about = "List snapshots")]
     List(porcelain::ListArgs),
+    #[command(about = "Snapshot and restore the DB automatically when switching Git branches")]
+    Hooks(hooks::HooksArgs),
 }
 
 pub const SCHEMAMAP_DEV_DB: &str = "schemamap_dev";
+pub const SCHEMAMAP_SCHEMA: &str = "schemamap";
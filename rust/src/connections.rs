@@ -0,0 +1,150 @@
+use clap::Parser;
+use console::{style, Emoji};
+use serde::Serialize;
+
+use crate::{
+    common::Cli,
+    parsers::{self, ConnectionSummary, DiscoveryOptions, Source},
+};
+
+static LOOKING_GLASS: Emoji<'_, '_> = Emoji("🔍 ", "");
+static CHECK: Emoji<'_, '_> = Emoji("✅ ", "");
+static CROSS: Emoji<'_, '_> = Emoji("❌ ", "");
+
+#[derive(Parser, Debug, Default, Clone)]
+pub struct ConnectionsArgs {
+    #[arg(
+        long,
+        help = "Output the probed connection sources as JSON",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set
+    )]
+    json: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct CandidateReport {
+    source: Source,
+    matched: bool,
+    used: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<ConnectionSummary>,
+}
+
+#[derive(Serialize)]
+struct ConnectionsReport {
+    used: Option<Source>,
+    // The winning candidate after --dbname/--username/--port overrides and PG* env defaults
+    effective: Option<ConnectionSummary>,
+    candidates: Vec<CandidateReport>,
+}
+
+fn build_report(opts: &DiscoveryOptions) -> ConnectionsReport {
+    let candidates = parsers::probe_all(opts);
+    let winner = candidates
+        .iter()
+        .find_map(|c| c.result.as_ref().ok().map(|config| (c.source, config)));
+    let used = winner.map(|(source, _)| source);
+
+    let effective = winner.map(|(source, config)| {
+        let mut config = config.clone();
+        opts.complete(&mut config, source);
+        ConnectionSummary::from(&config)
+    });
+
+    let candidates = candidates
+        .into_iter()
+        .map(|candidate| {
+            let is_used = Some(candidate.source) == used;
+            match candidate.result {
+                Ok(config) => CandidateReport {
+                    source: candidate.source,
+                    matched: true,
+                    used: is_used,
+                    error: None,
+                    connection: Some(ConnectionSummary::from(&config)),
+                },
+                Err(e) => CandidateReport {
+                    source: candidate.source,
+                    matched: false,
+                    used: false,
+                    error: Some(e.to_string()),
+                    connection: None,
+                },
+            }
+        })
+        .collect();
+
+    ConnectionsReport {
+        used,
+        effective,
+        candidates,
+    }
+}
+
+fn print_report(report: &ConnectionsReport) {
+    println!(
+        "{}",
+        style(format!(
+            "{}Connection sources, in priority order:",
+            LOOKING_GLASS
+        ))
+        .bold()
+    );
+
+    for candidate in &report.candidates {
+        let source = format!("{:<15}", candidate.source.to_string());
+        match (&candidate.connection, &candidate.error) {
+            (Some(connection), _) if candidate.used => {
+                println!(
+                    "  {}{} {} {}",
+                    CHECK,
+                    style(source).green().bold(),
+                    connection,
+                    style("<- used").green().bold()
+                );
+            }
+            (Some(connection), _) => {
+                println!("  {}{} {}", CHECK, source, connection);
+            }
+            (None, error) => {
+                println!(
+                    "  {}{} {}",
+                    CROSS,
+                    style(source).dim(),
+                    style(error.as_deref().unwrap_or_default()).dim()
+                );
+            }
+        }
+    }
+
+    println!();
+    match &report.effective {
+        Some(effective) => {
+            println!("Connecting with: {}", effective);
+            if !effective.guessed.is_empty() {
+                println!(
+                    "{}",
+                    style(format!("Guessed: {}", effective.guessed.join(", "))).yellow()
+                );
+            }
+        }
+        None => println!("{}No usable connection source found", CROSS),
+    }
+}
+
+pub async fn connections(cli: &Cli, args: &ConnectionsArgs) -> anyhow::Result<()> {
+    let report = build_report(&DiscoveryOptions::from_cli(cli)?);
+
+    if args.json.unwrap_or(false) {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
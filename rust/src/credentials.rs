@@ -0,0 +1,444 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use tokio_postgres::{config::Host, Client};
+
+use crate::common::Cli;
+use crate::config::Settings;
+use crate::init::{self, initialize_pgconfig};
+use crate::parsers::{self, PgConfig};
+use crate::porcelain;
+
+/// The roles of `create_schemamap_users.sql`, from most to least permissive.
+pub(crate) const SCHEMAMAP_ROLES: [&str; 4] = [
+    "schemamap",
+    "schemamap_readwrite",
+    "schemamap_readonly",
+    "schemamap_schema_read",
+];
+
+// Alphanumeric only, so it can be pasted into connection strings and .env files as is
+const PASSWORD_LENGTH: usize = 32;
+
+/// Where the role passwords come from, shared by `init` and `rotate-passwords`.
+#[derive(Args)]
+pub struct PasswordArgs {
+    #[arg(
+        long = "role-password",
+        value_name = "ROLE=PASSWORD",
+        help = "Password of an SDK role instead of a generated one, e.g. schemamap=secret. Can also be provided via SCHEMAMAP_PASSWORD, SCHEMAMAP_READONLY_PASSWORD, etc."
+    )]
+    role_passwords: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "pgpass-style file to write the role passwords to, only readable by you. Can also be provided via SCHEMAMAP_CREDENTIALS_FILE environment variable."
+    )]
+    credentials_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PasswordOrigin {
+    Flag,
+    Env,
+    CredentialsFile,
+    Generated,
+}
+
+#[derive(Debug)]
+pub(crate) struct RolePassword {
+    pub(crate) role: &'static str,
+    password: String,
+    origin: PasswordOrigin,
+}
+
+fn env_var_of(role: &str) -> &'static str {
+    match role {
+        "schemamap" => "SCHEMAMAP_PASSWORD",
+        "schemamap_readwrite" => "SCHEMAMAP_READWRITE_PASSWORD",
+        "schemamap_readonly" => "SCHEMAMAP_READONLY_PASSWORD",
+        _ => "SCHEMAMAP_SCHEMA_READ_PASSWORD",
+    }
+}
+
+pub(crate) fn generate_password() -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(PASSWORD_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Stored instead of the password, so it never shows up in the server logs.
+fn scram_verifier(password: &str) -> String {
+    postgres_protocol::password::scram_sha_256(password.as_bytes())
+}
+
+fn parse_role_password(arg: &str) -> Result<(&'static str, String)> {
+    let (role, password) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected ROLE=PASSWORD, got \"{}\"", arg))?;
+    let role = SCHEMAMAP_ROLES
+        .into_iter()
+        .find(|r| *r == role.trim())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown role \"{}\", expected one of: {}",
+                role,
+                SCHEMAMAP_ROLES.join(", ")
+            )
+        })?;
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("Empty password for role {}", role));
+    }
+    Ok((role, password.to_string()))
+}
+
+impl PasswordArgs {
+    pub(crate) fn credentials_file(&self, settings: &Settings) -> Option<PathBuf> {
+        self.credentials_file
+            .clone()
+            .or_else(|| settings.credentials_file.as_ref().map(|f| f.value.clone()))
+    }
+
+    /// Passwords from the flags, then env vars, then the credentials file if `reuse_file`, generated otherwise.
+    pub(crate) fn resolve(
+        &self,
+        roles: &[&'static str],
+        pgconfig: &PgConfig,
+        credentials_file: Option<&PathBuf>,
+        reuse_file: bool,
+    ) -> Result<Vec<RolePassword>> {
+        let flags = self
+            .role_passwords
+            .iter()
+            .map(|arg| parse_role_password(arg))
+            .collect::<Result<Vec<_>>>()?;
+        let stored = match credentials_file {
+            Some(path) if reuse_file && path.exists() => {
+                let (host, port) = credentials_server(pgconfig);
+                parsers::read_user_passwords(path, &host, port)?
+            }
+            _ => vec![],
+        };
+
+        Ok(roles
+            .iter()
+            .map(|&role| {
+                let env_var = env_var_of(role);
+                let (password, origin) =
+                    if let Some((_, p)) = flags.iter().find(|(r, _)| *r == role) {
+                        (p.clone(), PasswordOrigin::Flag)
+                    } else if let Some(p) = std::env::var(env_var).ok().filter(|p| !p.is_empty()) {
+                        log::debug!("Using the password of {} from {}", role, env_var);
+                        (p, PasswordOrigin::Env)
+                    } else if let Some((_, p)) = stored.iter().find(|(user, _)| user == role) {
+                        (p.clone(), PasswordOrigin::CredentialsFile)
+                    } else {
+                        (generate_password(), PasswordOrigin::Generated)
+                    };
+                RolePassword {
+                    role,
+                    password,
+                    origin,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Fills in the `:'<role>_password'` psql variables of `create_schemamap_users.sql`.
+pub(crate) fn render_users_sql(sql: &str, passwords: &[RolePassword]) -> String {
+    passwords.iter().fold(sql.to_string(), |sql, p| {
+        sql.replace(
+            &format!(":'{}_password'", p.role),
+            &format!("'{}'", scram_verifier(&p.password)),
+        )
+    })
+}
+
+fn alter_passwords_sql(passwords: &[RolePassword]) -> String {
+    passwords
+        .iter()
+        .map(|p| {
+            format!(
+                "alter role {} with password '{}';",
+                p.role,
+                scram_verifier(&p.password)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `host:port` the passwords of the credentials file are stored under.
+fn credentials_server(pgconfig: &PgConfig) -> (String, u16) {
+    // libpq matches `localhost` against the default socket directory too
+    let host = match pgconfig.get_hosts().first() {
+        Some(Host::Tcp(host)) => host.clone(),
+        _ => "localhost".to_string(),
+    };
+    (host, pgconfig.get_ports().first().copied().unwrap_or(5432))
+}
+
+/// Runs the password SQL, then saves the passwords. With --dry-run (no client) only the SQL and the
+/// generated passwords it sets are printed, the credentials file keeps the passwords the roles still have.
+pub(crate) async fn apply_passwords(
+    client: &Option<Client>,
+    sql: &str,
+    pgconfig: &PgConfig,
+    passwords: &[RolePassword],
+    credentials_file: Option<&PathBuf>,
+) -> Result<String> {
+    let Some(c) = client else {
+        println!("{}", sql);
+        return Ok(print_generated_passwords(passwords));
+    };
+    init::batch_in_transaction(c, sql).await?;
+    // The roles already have the new passwords, so they must not get lost
+    save_passwords(pgconfig, passwords, credentials_file).inspect_err(|_| {
+        eprintln!(
+            "Failed to save the new role passwords, store them now, they won't be shown again:"
+        );
+        for p in passwords {
+            eprintln!("  {}: {}", p.role, p.password);
+        }
+    })
+}
+
+/// Writes the passwords to the credentials file, or prints the ones only this run knows to stderr.
+fn save_passwords(
+    pgconfig: &PgConfig,
+    passwords: &[RolePassword],
+    credentials_file: Option<&PathBuf>,
+) -> Result<String> {
+    if let Some(path) = credentials_file {
+        let (host, port) = credentials_server(pgconfig);
+        let entries: Vec<(&str, &str)> = passwords
+            .iter()
+            .map(|p| (p.role, p.password.as_str()))
+            .collect();
+        parsers::write_user_passwords(path, &host, port, &entries)?;
+        log::info!(
+            "Wrote the passwords of {} role(s) to {}",
+            passwords.len(),
+            path.display()
+        );
+        return Ok(format!("passwords written to {}", path.display()));
+    }
+    Ok(print_generated_passwords(passwords))
+}
+
+fn print_generated_passwords(passwords: &[RolePassword]) -> String {
+    let generated: Vec<&RolePassword> = passwords
+        .iter()
+        .filter(|p| p.origin == PasswordOrigin::Generated)
+        .collect();
+    if generated.is_empty() {
+        return "passwords from flags/env".to_string();
+    }
+    // stderr, so they don't end up in the SQL of --dry-run or the --json summary
+    eprintln!("Generated role passwords, store them now, they won't be shown again:");
+    for p in &generated {
+        eprintln!("  {}: {}", p.role, p.password);
+    }
+    "generated passwords printed to stderr".to_string()
+}
+
+#[derive(Args)]
+pub struct RotatePasswordsArgs {
+    #[arg(
+        long,
+        value_name = "ROLE",
+        help = "Only rotate the password of this role, can be repeated [default: all SDK roles]"
+    )]
+    role: Vec<String>,
+
+    #[command(flatten)]
+    passwords: PasswordArgs,
+
+    #[arg(
+        long,
+        help = "Print SQL statements without executing them",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    pub(crate) dry_run: Option<bool>,
+}
+
+async fn existing_roles(client: &Client, roles: &[&'static str]) -> Result<Vec<&'static str>> {
+    let existing: Vec<String> = client
+        .query(
+            "select rolname::text from pg_roles where rolname = any($1)",
+            &[&roles],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    Ok(roles
+        .iter()
+        .copied()
+        .filter(|role| existing.iter().any(|e| e == role))
+        .collect())
+}
+
+pub async fn rotate_passwords(cli: &Cli, args: &RotatePasswordsArgs) -> Result<()> {
+    let dry_run = args.dry_run.unwrap_or(false);
+    let settings = Settings::resolve(cli)?;
+
+    let roles: Vec<&'static str> = if args.role.is_empty() {
+        SCHEMAMAP_ROLES.to_vec()
+    } else {
+        args.role
+            .iter()
+            .map(|role| {
+                SCHEMAMAP_ROLES
+                    .into_iter()
+                    .find(|r| r == role)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown role \"{}\", expected one of: {}",
+                            role,
+                            SCHEMAMAP_ROLES.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let interactive = atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stdin);
+    let mut pgconfig = initialize_pgconfig(cli, interactive);
+
+    let client = if dry_run {
+        None
+    } else {
+        Some(porcelain::connect_with_prompt(&mut pgconfig, interactive).await?)
+    };
+    init::ensure_primary(&client).await?;
+
+    let roles = match &client {
+        Some(c) => existing_roles(c, &roles).await?,
+        None => roles,
+    };
+    if roles.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The Schemamap.io SDK roles don't exist, run `schemamap init` first"
+        ));
+    }
+
+    let credentials_file = args.passwords.credentials_file(&settings);
+    // The stored passwords are the ones being rotated
+    let passwords = args
+        .passwords
+        .resolve(&roles, &pgconfig, credentials_file.as_ref(), false)?;
+    let sql = alter_passwords_sql(&passwords);
+
+    apply_passwords(
+        &client,
+        &sql,
+        &pgconfig,
+        &passwords,
+        credentials_file.as_ref(),
+    )
+    .await?;
+    if client.is_some() {
+        log::info!("Rotated the passwords of {}", roles.join(", "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_users_sql() {
+        let password = generate_password();
+        assert_eq!(password.len(), PASSWORD_LENGTH);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(password, generate_password());
+
+        let passwords = vec![RolePassword {
+            role: "schemamap",
+            password,
+            origin: PasswordOrigin::Generated,
+        }];
+        let sql = render_users_sql(
+            "create user schemamap password :'schemamap_password';",
+            &passwords,
+        );
+        assert!(sql.starts_with("create user schemamap password 'SCRAM-SHA-256$4096:"));
+        assert!(!sql.contains(&passwords[0].password));
+
+        assert_eq!(
+            parse_role_password("schemamap_readonly=se=cret").unwrap(),
+            ("schemamap_readonly", "se=cret".to_string())
+        );
+        assert!(parse_role_password("postgres=secret").is_err());
+        assert!(parse_role_password("schemamap").is_err());
+    }
+
+    #[test]
+    fn test_resolve_reuses_passwords_of_the_server() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("credentials");
+        parsers::write_user_passwords(&path, "prod", 5432, &[("schemamap", "prod")]).unwrap();
+        parsers::write_user_passwords(&path, "localhost", 5432, &[("schemamap", "local")]).unwrap();
+        let args = PasswordArgs {
+            role_passwords: vec![],
+            credentials_file: None,
+        };
+        let resolve = |conn: &str| {
+            let pgconfig = PgConfig::new(conn.parse().unwrap(), Default::default());
+            args.resolve(&["schemamap"], &pgconfig, Some(&path), true)
+                .unwrap()
+                .remove(0)
+        };
+
+        let local = resolve("host=localhost");
+        assert_eq!(local.password, "local");
+        assert_eq!(local.origin, PasswordOrigin::CredentialsFile);
+        assert_eq!(resolve("host=prod").password, "prod");
+        assert_eq!(
+            resolve("host=prod port=5433").origin,
+            PasswordOrigin::Generated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_rotation_keeps_credentials_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("credentials");
+        let stored = "localhost:5432:*:schemamap:current\n";
+        parsers::write_user_passwords(&path, "localhost", 5432, &[("schemamap", "current")])
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), stored);
+
+        let pgconfig = PgConfig::new(tokio_postgres::Config::new(), Default::default());
+        let passwords = PasswordArgs {
+            role_passwords: vec![],
+            credentials_file: None,
+        }
+        .resolve(&["schemamap"], &pgconfig, Some(&path), false)
+        .unwrap();
+        assert_eq!(passwords[0].origin, PasswordOrigin::Generated);
+        apply_passwords(
+            &None,
+            &alter_passwords_sql(&passwords),
+            &pgconfig,
+            &passwords,
+            Some(&path),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), stored);
+    }
+}
@@ -5,7 +5,13 @@ use clap::Parser;
 use console::{style, Emoji};
 use serde_json::to_string_pretty;
 
-use crate::{common::Cli, porcelain::connect, up};
+use crate::{
+    common::{Cli, SCHEMAMAP_SCHEMA},
+    config::Settings,
+    migrations::in_schema,
+    porcelain::connect,
+    up,
+};
 
 static LOOKING_GLASS: Emoji<'_, '_> = Emoji("🔍 ", "");
 static CHECK: Emoji<'_, '_> = Emoji("✅ ", "");
@@ -22,23 +28,29 @@ fn print_check(message: &str, check_result: bool) {
     }
 }
 
-async fn check_if_schemamap_schema_exists(client: &tokio_postgres::Client) -> anyhow::Result<bool> {
-    let schemamap_schema_exists: bool = client
-        .simple_query(
+async fn check_if_schemamap_schema_exists(
+    client: &tokio_postgres::Client,
+    schema: &str,
+) -> anyhow::Result<bool> {
+    let schemamap_schema_exists: bool = !client
+        .query(
             "SELECT schema_name
             FROM information_schema.schemata
-            WHERE schema_name = 'schemamap'
+            WHERE schema_name = $1
             LIMIT 1;",
+            &[&schema],
         )
         .await?
-        .get(0)
-        .is_some();
+        .is_empty();
 
-    print_check("`schemamap` schema exists", schemamap_schema_exists);
+    print_check(
+        &format!("`{}` schema exists", schema),
+        schemamap_schema_exists,
+    );
     if !schemamap_schema_exists {
         println!(
-            "{} Schemamap schema not found, please run `schemamap init` first.",
-            CROSS
+            "{} Schemamap schema `{}` not found, please run `schemamap init` first.",
+            CROSS, schema
         );
         exit(1);
     }
@@ -54,7 +66,10 @@ lazy_static! {
     ]);
 }
 
-async fn check_schemamap_roles(client: &tokio_postgres::Client) -> anyhow::Result<bool> {
+async fn check_schemamap_roles(
+    client: &tokio_postgres::Client,
+    schema: &str,
+) -> anyhow::Result<bool> {
     let role_check_sql = "
   WITH RECURSIVE role_hierarchy AS (
     SELECT
@@ -94,7 +109,7 @@ privileges_agg AS (
         ON r.member_of = g.grantee AND
            g.table_schema IS NOT NULL AND
            g.privilege_type IS NOT NULL AND
-           g.table_schema != 'schemamap'
+           g.table_schema != $1
     GROUP BY
         r.role_name, g.table_schema, g.privilege_type
 ),
@@ -131,7 +146,7 @@ FROM
     final_agg
 ORDER BY 1;";
 
-    let resultset = client.query(role_check_sql, &[]).await?;
+    let resultset = client.query(role_check_sql, &[&schema]).await?;
     println!(
         "{}",
         style(format!("{} Checking schemamap roles:", LOOKING_GLASS)).bold()
@@ -146,12 +161,9 @@ ORDER BY 1;";
 
         let indent = "  ";
         println!("{}{} role: {}", indent, CHECK, role_name);
-        match privileges {
-            Some(privileges) => {
-                println!("{}{} GRANTs by schema and type:", indent, LOCK);
-                println!("{}{}", indent, to_string_pretty(&privileges).unwrap());
-            }
-            None => {}
+        if let Some(privileges) = privileges {
+            println!("{}{} GRANTs by schema and type:", indent, LOCK);
+            println!("{}{}", indent, to_string_pretty(&privileges).unwrap());
         }
     });
 
@@ -173,8 +185,53 @@ ORDER BY 1;";
     Ok(true)
 }
 
-async fn check_if_tunnel_config_exists() -> anyhow::Result<bool> {
-    let first_existing_filepath = up::find_first_existing_tunnel_config_file(&vec![None]);
+// Installs of earlier versions granted the whole SDK to PUBLIC, i.e. every role of the DB
+async fn check_public_grants(
+    client: &tokio_postgres::Client,
+    schema: &str,
+) -> anyhow::Result<bool> {
+    let row = client
+        .query_one(
+            "SELECT
+              has_schema_privilege('public', $1, 'USAGE')
+                OR has_schema_privilege('public', $1, 'CREATE') AS schema_granted,
+              EXISTS (
+                SELECT 1
+                FROM pg_default_acl d
+                JOIN pg_namespace n ON n.oid = d.defaclnamespace,
+                LATERAL aclexplode(d.defaclacl) acl
+                WHERE n.nspname = $1 AND acl.grantee = 0
+              ) AS default_granted",
+            &[&schema],
+        )
+        .await?;
+    let schema_granted: bool = row.get("schema_granted");
+    let default_granted: bool = row.get("default_granted");
+
+    let public_granted = schema_granted || default_granted;
+    print_check(
+        &format!("`{}` schema is not granted to PUBLIC", schema),
+        !public_granted,
+    );
+    if public_granted {
+        println!(
+            "{} The `{}` schema is granted to PUBLIC, so every role in the DB can use it.",
+            WARN, schema
+        );
+        println!("  Grant it to your application roles only, revoking the PUBLIC grants:");
+        let schema_flag = if schema == SCHEMAMAP_SCHEMA {
+            String::new()
+        } else {
+            format!(" --schema {}", schema)
+        };
+        println!("  schemamap init --app-role <your_app_role>{}", schema_flag);
+    }
+    Ok(!public_granted)
+}
+
+async fn check_if_tunnel_config_exists(cli: &Cli) -> anyhow::Result<bool> {
+    let tunnel_config = Settings::resolve(cli)?.tunnel_config.map(|s| s.value);
+    let first_existing_filepath = up::find_first_existing_tunnel_config_file(&[tunnel_config]);
     let file_exists = first_existing_filepath.is_some();
 
     if file_exists {
@@ -204,10 +261,16 @@ fn indent_lines(text: &str, indent: &str) -> String {
         .join("\n")
 }
 
-async fn schemamap_verify_installation(client: &tokio_postgres::Client) -> anyhow::Result<bool> {
+async fn schemamap_verify_installation(
+    client: &tokio_postgres::Client,
+    schema: &str,
+) -> anyhow::Result<bool> {
     let row = client
         .query_one(
-            "SELECT tenants_defined, mdes_defined FROM schemamap.verify_installation()",
+            &format!(
+                "SELECT tenants_defined, mdes_defined FROM {}.verify_installation()",
+                schema
+            ),
             &[],
         )
         .await;
@@ -227,7 +290,7 @@ async fn schemamap_verify_installation(client: &tokio_postgres::Client) -> anyho
     let indent = "  ";
     if !tenants_defined {
         println!("{} Tenants are not defined", WARN);
-        println!("{}{}", indent, "To allow for tenant-aware data migrations, you can teach Schemamap.io how you model your tenants via a SELECT query.");
+        println!("{}To allow for tenant-aware data migrations, you can teach Schemamap.io how you model your tenants via a SELECT query.", indent);
         println!();
         let sample_tenant_listing_definition = r##"select schemamap.update_function_definition('list_tenants', $$
  select
@@ -239,14 +302,17 @@ async fn schemamap_verify_installation(client: &tokio_postgres::Client) -> anyho
 from tenants; -- or: organizations/users/etc.
 $$);"##;
 
-        println!("{}", indent_lines(sample_tenant_listing_definition, indent));
+        println!(
+            "{}",
+            indent_lines(&in_schema(sample_tenant_listing_definition, schema), indent)
+        );
         println!();
     }
 
     if !mdes_defined {
         println!("{} Master Data Entities (MDEs) not defined", WARN);
-        println!("{}{}", indent, "MDEs make it easy to do data migrations on tables with natural keys (via unique constraints), that belong together.");
-        println!("{}{}", indent, "As an example:");
+        println!("{}MDEs make it easy to do data migrations on tables with natural keys (via unique constraints), that belong together.", indent);
+        println!("{}As an example:", indent);
         println!();
 
         let sample_mde_definition = r##"select schemamap.define_master_data_entity('products', $$
@@ -257,7 +323,10 @@ $$);"##;
   left join product_categories pc on pc.product_id = p.id and false
   where p.deleted_at is null -- or any other filtering which makes sense for your domain
 $$);"##;
-        println!("{}", indent_lines(sample_mde_definition, indent));
+        println!(
+            "{}",
+            indent_lines(&in_schema(sample_mde_definition, schema), indent)
+        );
         println!();
 
         println!("{}NOTE: we use `false` in the join conditions to avoid a cartesian product of all tables, mainly for has-many relations.", indent);
@@ -270,17 +339,20 @@ $$);"##;
 
 // Similar to `doom doctor`
 pub(crate) async fn doctor(cli: &Cli, _args: &DoctorArgs) -> anyhow::Result<()> {
+    let schema = Settings::resolve(cli)?.schema.value;
     let client = connect(cli).await?;
 
     println!("{}Checking Schemamap SDK...", LOOKING_GLASS);
 
-    check_if_schemamap_schema_exists(&client).await?;
+    check_if_schemamap_schema_exists(&client, &schema).await?;
+
+    check_schemamap_roles(&client, &schema).await?;
 
-    check_schemamap_roles(&client).await?;
+    check_public_grants(&client, &schema).await?;
 
-    schemamap_verify_installation(&client).await?;
+    schemamap_verify_installation(&client, &schema).await?;
 
-    check_if_tunnel_config_exists().await?;
+    check_if_tunnel_config_exists(cli).await?;
 
     Ok(())
 }
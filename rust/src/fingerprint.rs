@@ -0,0 +1,197 @@
+use anyhow::Result;
+use tokio_postgres::Client;
+
+/// Hash of the tables, columns, constraints and indexes outside of the SDK schema.
+/// Two DBs with the same fingerprint can be restored into each other without re-running migrations.
+const CATALOG_FINGERPRINT_SQL: &str = "
+select md5(coalesce(string_agg(def, E'\\n' order by def), ''))
+from (
+  select format('%I.%I.%I %s%s', n.nspname, c.relname, a.attname,
+                format_type(a.atttypid, a.atttypmod),
+                case when a.attnotnull then ' not null' else '' end) as def
+  from pg_attribute a
+  join pg_class c on c.oid = a.attrelid
+  join pg_namespace n on n.oid = c.relnamespace
+  where a.attnum > 0 and not a.attisdropped and
+        c.relkind in ('r', 'p', 'v', 'm', 'f') and
+        n.nspname <> all($1) and n.nspname not like 'pg\\_%'
+  union all
+  select format('%I.%I %s', n.nspname, c.relname, pg_get_constraintdef(co.oid))
+  from pg_constraint co
+  join pg_class c on c.oid = co.conrelid
+  join pg_namespace n on n.oid = c.relnamespace
+  where n.nspname <> all($1) and n.nspname not like 'pg\\_%'
+  union all
+  select pg_get_indexdef(i.indexrelid)
+  from pg_index i
+  join pg_class c on c.oid = i.indrelid
+  join pg_namespace n on n.oid = c.relnamespace
+  where n.nspname <> all($1) and n.nspname not like 'pg\\_%'
+) defs";
+
+// In order of preference, e.g. Django's django_migrations has both `id` and `name`
+const VERSION_COLUMNS: [&str; 5] = [
+    "version",
+    "version_num",
+    "version_id",
+    "migration_name",
+    "name",
+];
+
+// When the migrations were applied, in order of preference, e.g. Flyway's `installed_rank` over `installed_on`
+const APPLIED_ORDER_COLUMNS: [&str; 9] = [
+    "installed_rank",
+    "applied",
+    "applied_at",
+    "installed_on",
+    "inserted_at",
+    "run_on",
+    "migration_time",
+    "finished_at",
+    "tstamp",
+];
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct SchemaFingerprint {
+    pub(crate) fingerprint: String,
+    /// `<schema>.<table>=<latest version>` of each schema migration table, if the SDK is installed
+    pub(crate) migration_version: Option<String>,
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Selects the last applied version of a migration table, or the number of migrations if it has no version column.
+/// Versions are text in some tools, so without an applied-at column longer versions sort later (Flyway's `10` after `9`).
+fn latest_version_sql(schema_name: &str, table_name: &str, columns: &[String]) -> String {
+    let find_column = |candidates: &[&'static str]| {
+        candidates
+            .iter()
+            .copied()
+            .find(|c| columns.iter().any(|column| column == c))
+    };
+    let table = format!("{}.{}", quote_ident(schema_name), quote_ident(table_name));
+    let Some(version) = find_column(&VERSION_COLUMNS).map(quote_ident) else {
+        return format!("select count(*)::text from {}", table);
+    };
+    let order = match find_column(&APPLIED_ORDER_COLUMNS) {
+        Some(applied) => format!("{} desc", quote_ident(applied)),
+        None => format!("length({0}::text) desc, {0}::text desc", version),
+    };
+    format!(
+        "select {0}::text from {1} where {0} is not null order by {2} limit 1",
+        version, table, order
+    )
+}
+
+async fn migration_version(client: &Client, sdk_schema: &str) -> Result<Option<String>> {
+    let installed: bool = client
+        .query_one(
+            "select to_regclass($1 || '.columns') is not null",
+            &[&sdk_schema],
+        )
+        .await?
+        .get(0);
+    if !installed {
+        return Ok(None);
+    }
+
+    // Tables matched by the `schema_migration_table` concept, with their columns
+    let tables = client
+        .query(
+            &format!(
+                "select schema_name::text, table_name::text, array_agg(column_name::text)
+                 from {}.columns
+                 where is_schema_migration_table
+                 group by 1, 2
+                 order by 1, 2",
+                sdk_schema
+            ),
+            &[],
+        )
+        .await?;
+
+    let mut versions = vec![];
+    for table in &tables {
+        let (schema_name, table_name): (String, String) = (table.get(0), table.get(1));
+        let columns: Vec<String> = table.get(2);
+        let latest: Option<String> = client
+            .query_opt(
+                &latest_version_sql(&schema_name, &table_name, &columns),
+                &[],
+            )
+            .await?
+            .map(|row| row.get(0));
+        versions.push(format!(
+            "{}.{}={}",
+            schema_name,
+            table_name,
+            latest.unwrap_or_default()
+        ));
+    }
+
+    Ok(if versions.is_empty() {
+        None
+    } else {
+        Some(versions.join(", "))
+    })
+}
+
+/// The SDK schema is left out, it changes with the CLI version rather than the application code.
+pub(crate) async fn schema_fingerprint(
+    client: &Client,
+    sdk_schema: &str,
+) -> Result<SchemaFingerprint> {
+    let ignored_schemas = vec!["information_schema", sdk_schema];
+    let fingerprint: String = client
+        .query_one(CATALOG_FINGERPRINT_SQL, &[&ignored_schemas])
+        .await?
+        .get(0);
+
+    Ok(SchemaFingerprint {
+        fingerprint,
+        migration_version: migration_version(client, sdk_schema).await?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("schema_migrations"), "\"schema_migrations\"");
+        assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+    }
+
+    #[test]
+    fn test_latest_version_sql() {
+        let columns = |names: &[&str]| names.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            latest_version_sql(
+                "public",
+                "flyway_schema_history",
+                &columns(&["installed_rank", "version", "installed_on"])
+            ),
+            "select \"version\"::text from \"public\".\"flyway_schema_history\" where \"version\" is not null order by \"installed_rank\" desc limit 1"
+        );
+        assert_eq!(
+            latest_version_sql(
+                "public",
+                "django_migrations",
+                &columns(&["id", "app", "name", "applied"])
+            ),
+            "select \"name\"::text from \"public\".\"django_migrations\" where \"name\" is not null order by \"applied\" desc limit 1"
+        );
+        assert_eq!(
+            latest_version_sql("public", "schema_migrations", &columns(&["version"])),
+            "select \"version\"::text from \"public\".\"schema_migrations\" where \"version\" is not null order by length(\"version\"::text) desc, \"version\"::text desc limit 1"
+        );
+        assert_eq!(
+            latest_version_sql("public", "migrations", &columns(&["id", "filename"])),
+            "select count(*)::text from \"public\".\"migrations\""
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use dialoguer::theme::ColorfulTheme;
+use git2::{BranchType, Repository, RepositoryState};
+
+use crate::common::Cli;
+use crate::config::{self, Settings};
+use crate::parsers;
+use crate::porcelain::{self, GitStats, RestoreArgs, SnapshotArgs};
+
+// Identifies the hooks we own, so others are never overwritten or removed by accident
+const HOOK_MARKER: &str = "# Installed by `schemamap hooks install`";
+const HOOK_NAMES: [&str; 2] = ["post-checkout", "pre-rebase"];
+const HOOK_TAG: &str = "hook";
+
+#[derive(Parser, Debug, Clone)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    command: HooksCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum HooksCommands {
+    #[command(
+        about = "Install post-checkout/pre-rebase hooks that snapshot the DB of the branch being left and offer to restore the one being entered"
+    )]
+    Install(InstallArgs),
+    #[command(about = "Remove the hooks installed by `schemamap hooks install`")]
+    Uninstall,
+    // Called by the installed hooks with the arguments Git passes them
+    #[command(hide = true)]
+    PostCheckout {
+        previous_head: String,
+        new_head: String,
+        branch_checkout: String,
+    },
+    #[command(hide = true)]
+    PreRebase {
+        upstream: String,
+        branch: Option<String>,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+struct InstallArgs {
+    #[arg(
+        long,
+        help = "Replace existing hooks not installed by schemamap, keeping them as <hook>.backup",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    force: Option<bool>,
+}
+
+fn hook_script(hook: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+{}, remove with `schemamap hooks uninstall`
+command -v schemamap >/dev/null 2>&1 || exit 0
+# Git doesn't give hooks the terminal, reattach it so schemamap can ask before restoring a snapshot
+if (exec < /dev/tty) 2>/dev/null; then exec < /dev/tty; fi
+# Never fail the Git command because of the DB
+schemamap hooks {} "$@" || echo "schemamap: {} hook failed, see above" >&2
+exit 0
+"#,
+        HOOK_MARKER, hook, hook
+    )
+}
+
+// git2 has no Repository::commondir yet, linked worktrees point at the main .git directory in their `commondir` file
+fn common_dir(repo: &Repository) -> PathBuf {
+    let path = repo.path();
+    match std::fs::read_to_string(path.join("commondir")) {
+        Ok(common_dir) => {
+            let common_dir = path.join(common_dir.trim());
+            common_dir.canonicalize().unwrap_or(common_dir)
+        }
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|c| c.get_path("core.hooksPath").ok());
+    Ok(match configured {
+        // Relative to the worktree, like Git does
+        Some(path) if path.is_relative() => repo
+            .workdir()
+            .ok_or_else(|| anyhow::anyhow!("Bare Git repositories have no checkouts to hook into"))?
+            .join(path),
+        Some(path) => path,
+        // Shared by all worktrees
+        None => common_dir(repo).join("hooks"),
+    })
+}
+
+fn is_ours(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|script| script.contains(HOOK_MARKER))
+}
+
+fn write_executable(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn backup_path(dir: &Path, hook: &str) -> PathBuf {
+    dir.join(format!("{}.backup", hook))
+}
+
+fn install(args: &InstallArgs) -> Result<()> {
+    let repo = Repository::discover(".")?;
+    let dir = hooks_dir(&repo)?;
+    std::fs::create_dir_all(&dir)?;
+    let force = args.force.unwrap_or(false);
+
+    // Check all of them first, so a conflict doesn't leave only some installed
+    let foreign: Vec<&str> = HOOK_NAMES
+        .into_iter()
+        .filter(|hook| dir.join(hook).exists() && !is_ours(&dir.join(hook)))
+        .collect();
+    if !foreign.is_empty() && !force {
+        return Err(anyhow::anyhow!(
+            "Found hooks not installed by schemamap in {1}: {0}. Call `schemamap hooks <hook> \"$@\"` from them, or use --force to replace them",
+            foreign.join(", "),
+            dir.display()
+        ));
+    }
+    if let Some(backup) = foreign
+        .iter()
+        .map(|hook| backup_path(&dir, hook))
+        .find(|backup| backup.exists())
+    {
+        return Err(anyhow::anyhow!(
+            "{} already exists, move it away before replacing the hook with --force",
+            backup.display()
+        ));
+    }
+
+    for hook in HOOK_NAMES {
+        let path = dir.join(hook);
+        if foreign.contains(&hook) {
+            let backup = backup_path(&dir, hook);
+            std::fs::rename(&path, &backup)?;
+            log::info!("Moved the existing {} hook to {}", hook, backup.display());
+        }
+        write_executable(&path, &hook_script(hook))?;
+        log::info!("Installed {}", path.display());
+    }
+    Ok(())
+}
+
+fn uninstall() -> Result<()> {
+    let repo = Repository::discover(".")?;
+    let dir = hooks_dir(&repo)?;
+
+    for hook in HOOK_NAMES {
+        let path = dir.join(hook);
+        if !is_ours(&path) {
+            continue;
+        }
+        std::fs::remove_file(&path)?;
+        let backup = backup_path(&dir, hook);
+        if backup.exists() {
+            std::fs::rename(&backup, &path)?;
+            log::info!("Restored the previous {} hook", hook);
+        } else {
+            log::info!("Removed {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// The branches of the latest `checkout: moving from <from> to <to>` HEAD reflog entry.
+fn parse_checkout_reflog(message: &str) -> Option<(&str, &str)> {
+    message
+        .strip_prefix("checkout: moving from ")?
+        .split_once(" to ")
+}
+
+fn default_snapshot_name(cli: &Cli, db_name: &str, git_stats: &GitStats) -> Result<String> {
+    Ok(config::render_snapshot_name(
+        &Settings::resolve(cli)?.snapshot_name_template.value,
+        db_name,
+        &git_stats.branch_name,
+        &git_stats.revision,
+    ))
+}
+
+// Replaced on every checkout, so it must not be the name `schemamap snapshot` gives the user's own snapshots
+fn hook_snapshot_name(cli: &Cli, db_name: &str, git_stats: &GitStats) -> Result<String> {
+    Ok(format!(
+        "{}_{}",
+        default_snapshot_name(cli, db_name, git_stats)?,
+        HOOK_TAG
+    ))
+}
+
+async fn snapshot_branch(
+    cli: &Cli,
+    db_name: &str,
+    git_stats: &GitStats,
+    message: String,
+) -> Result<String> {
+    let args = SnapshotArgs {
+        snapshot_name: Some(hook_snapshot_name(cli, db_name, git_stats)?),
+        message: Some(message),
+        tag: vec![HOOK_TAG.to_string()],
+        ..Default::default()
+    };
+    porcelain::create_snapshot(cli, &args, git_stats).await
+}
+
+fn confirm_restore(branch: &str, snapshot_name: &str) -> bool {
+    dialoguer::Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Restore the DB of the {} branch from the \"{}\" snapshot?",
+            branch, snapshot_name
+        ))
+        .default(true)
+        .interact()
+        .unwrap_or(false)
+}
+
+async fn post_checkout(
+    cli: &Cli,
+    previous_head: &str,
+    new_head: &str,
+    branch_checkout: &str,
+) -> Result<()> {
+    // File checkouts don't change the branch, rebases and bisects check out commits on their own
+    let repo = Repository::discover(".")?;
+    if branch_checkout != "1" || repo.state() != RepositoryState::Clean {
+        return Ok(());
+    }
+
+    let head = repo.head()?;
+    let Some(branch) = head.shorthand().filter(|_| head.is_branch()) else {
+        return Ok(());
+    };
+    let reflog = repo.reflog("HEAD")?;
+    let last_entry = reflog
+        .get(0)
+        .and_then(|entry| entry.message().map(str::to_string));
+    let Some((previous_branch, _)) = last_entry.as_deref().and_then(parse_checkout_reflog) else {
+        return Ok(());
+    };
+    if previous_branch == branch
+        || repo
+            .find_branch(previous_branch, BranchType::Local)
+            .is_err()
+    {
+        return Ok(());
+    }
+
+    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    let db_name = pgconfig.get_dbname().unwrap_or("postgres");
+
+    // The DB still has the schema and data of the branch being left
+    let left = GitStats {
+        branch_name: previous_branch.to_string(),
+        revision: previous_head.to_string(),
+    };
+    let saved = snapshot_branch(
+        cli,
+        db_name,
+        &left,
+        format!("Left {} for {}", previous_branch, branch),
+    )
+    .await?;
+    log::info!("Saved the DB of {} to: {}", previous_branch, saved);
+
+    // The state the branch was left in, else a snapshot the user took of it
+    let entered = GitStats {
+        branch_name: branch.to_string(),
+        revision: new_head.to_string(),
+    };
+    let client = porcelain::connect_to_schemamap_dev(cli).await?;
+    let mut snapshot_name = None;
+    for name in [
+        hook_snapshot_name(cli, db_name, &entered)?,
+        default_snapshot_name(cli, db_name, &entered)?,
+    ] {
+        if porcelain::snapshot_exists(&client, &name).await? {
+            snapshot_name = Some(name);
+            break;
+        }
+    }
+    let Some(snapshot_name) = snapshot_name else {
+        log::info!(
+            "No snapshot of {} yet, run your migrations if its schema differs from {}",
+            branch,
+            previous_branch
+        );
+        return Ok(());
+    };
+
+    let interactive = atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout);
+    if !interactive || !confirm_restore(branch, &snapshot_name) {
+        println!(
+            "schemamap: run `schemamap restore {}` to switch the DB to {}",
+            snapshot_name, branch
+        );
+        return Ok(());
+    }
+
+    // The snapshot just taken is the backup, no need for an autosave too
+    let restore_args = RestoreArgs {
+        snapshot_name: Some(snapshot_name),
+        input: Some(false),
+        autosave: Some(false),
+        ..Default::default()
+    };
+    porcelain::restore(cli, &restore_args).await.map_err(|e| {
+        e.context(format!(
+            "Run `schemamap restore {}` to get the DB of {} back",
+            saved, previous_branch
+        ))
+    })
+}
+
+async fn pre_rebase(cli: &Cli, upstream: &str, branch: Option<&str>) -> Result<()> {
+    // The DB has the state of the checked out branch, whichever branch is rebased
+    let git_stats = porcelain::current_git_stats()?;
+    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    let saved = snapshot_branch(
+        cli,
+        pgconfig.get_dbname().unwrap_or("postgres"),
+        &git_stats,
+        format!(
+            "Before rebasing {} onto {}",
+            branch.unwrap_or(&git_stats.branch_name),
+            upstream
+        ),
+    )
+    .await?;
+    log::info!("Saved the DB of {} to: {}", git_stats.branch_name, saved);
+    Ok(())
+}
+
+pub async fn hooks(cli: &Cli, args: &HooksArgs) -> Result<()> {
+    match &args.command {
+        HooksCommands::Install(install_args) => install(install_args),
+        HooksCommands::Uninstall => uninstall(),
+        HooksCommands::PostCheckout {
+            previous_head,
+            new_head,
+            branch_checkout,
+        } => post_checkout(cli, previous_head, new_head, branch_checkout).await,
+        HooksCommands::PreRebase { upstream, branch } => {
+            pre_rebase(cli, upstream, branch.as_deref()).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checkout_reflog() {
+        assert_eq!(
+            parse_checkout_reflog("checkout: moving from main to feature/orders"),
+            Some(("main", "feature/orders"))
+        );
+        assert_eq!(parse_checkout_reflog("commit: add orders"), None);
+
+        let script = hook_script("post-checkout");
+        assert!(script.contains(HOOK_MARKER));
+        assert!(script.contains("schemamap hooks post-checkout \"$@\""));
+    }
+}
@@ -1,17 +1,37 @@
+use std::path::PathBuf;
+
 use crate::common::{Cli, SCHEMAMAP_DEV_DB};
+use crate::config::{valid_pg_identifier, Settings};
 use anyhow::Result;
 use clap::Args;
+use console::{style, Emoji};
 use dialoguer::theme::ColorfulTheme;
-use tokio_postgres::{Client, Config, NoTls};
+use serde::Serialize;
+use tokio_postgres::{Client, Config};
 
-use crate::parsers;
+use crate::credentials::{self, PasswordArgs, SCHEMAMAP_ROLES};
+use crate::migrations::{self, in_schema};
+use crate::parsers::{self, PgConfig};
+use crate::porcelain;
 
 const CREATE_SCHEMAMAP_USERS_SQL: &str = include_str!("../create_schemamap_users.sql");
-const CREATE_SCHEMAMAP_SCHEMA_SQL: &str = include_str!("../create_schemamap_schema.sql");
 const GRANT_SCHEMAMAP_USAGE_SQL: &str = include_str!("../grant_schemamap_usage.sql");
+const GRANT_SCHEMAMAP_APP_ROLE_SQL: &str = include_str!("../grant_schemamap_app_role.sql");
+
+// Earlier versions granted the SDK to PUBLIC, undone once the application roles are known
+const REVOKE_PUBLIC_SQL: &str = "grant schemamap to CURRENT_USER;
+revoke usage, create on schema schemamap from PUBLIC;
+alter default privileges in schema schemamap revoke execute on functions from PUBLIC;
+alter default privileges in schema schemamap revoke select on tables from PUBLIC;
+revoke all on schemamap.data_migrations from PUBLIC;
+revoke schemamap from CURRENT_USER;";
 
 const SCHEMAMAP_DEV_SQL: &str = include_str!("../schemamap_dev.sql");
 
+static CHECK: Emoji<'_, '_> = Emoji("✅ ", "");
+static SKIP: Emoji<'_, '_> = Emoji("⏭️  ", "");
+static CROSS: Emoji<'_, '_> = Emoji("❌ ", "");
+
 // Closely simulating psql cli arguments
 #[derive(Args)]
 pub struct InitArgs {
@@ -46,15 +66,141 @@ pub struct InitArgs {
         action = clap::ArgAction::Set,
       )]
     pub(crate) dry_run: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Print a summary of the steps as JSON, for CI pipelines",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    json: Option<bool>,
+
+    #[arg(
+        long = "app-role",
+        value_name = "ROLE",
+        help = "Application DB role to grant the SDK to, can be repeated. Can also be provided via SCHEMAMAP_APP_ROLES environment variable."
+    )]
+    app_roles: Vec<String>,
+
+    #[command(flatten)]
+    passwords: PasswordArgs,
+}
+
+/// What an init step did, when it didn't fail.
+pub(crate) enum Outcome {
+    Done(Option<String>),
+    Skipped(String),
 }
 
-pub(crate) fn initialize_pgconfig(cli: &Cli, interactive: bool) -> tokio_postgres::Config {
-    let pgconfig =
-            parsers::parse_pgconfig(cli.dbname.clone(), cli.username.clone(), cli.conn.clone(), cli.port)
-            .unwrap_or_else(|_e| {
+impl From<()> for Outcome {
+    fn from(_: ()) -> Self {
+        Outcome::Done(None)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum StepStatus {
+    Succeeded,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+struct StepReport {
+    step: &'static str,
+    status: StepStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// The steps of an init run, stopping at the first failure.
+#[derive(Debug, Default, Serialize)]
+struct InitSummary {
+    dbname: String,
+    success: bool,
+    steps: Vec<StepReport>,
+}
+
+impl InitSummary {
+    fn failed(&self) -> bool {
+        self.steps.iter().any(|s| s.status == StepStatus::Failed)
+    }
+
+    fn push(&mut self, step: &'static str, status: StepStatus, detail: Option<String>) {
+        self.steps.push(StepReport {
+            step,
+            status,
+            detail,
+        });
+        self.success = !self.failed();
+    }
+
+    fn skip(&mut self, step: &'static str, reason: &str) {
+        self.push(step, StepStatus::Skipped, Some(reason.to_string()));
+    }
+
+    /// Runs the step unless an earlier one failed.
+    async fn step<T: Into<Outcome>>(
+        &mut self,
+        step: &'static str,
+        run: impl std::future::Future<Output = Result<T>>,
+    ) {
+        if self.failed() {
+            return self.skip(step, "an earlier step failed");
+        }
+        match run.await.map(Into::into) {
+            Ok(Outcome::Done(detail)) => self.push(step, StepStatus::Succeeded, detail),
+            Ok(Outcome::Skipped(reason)) => self.push(step, StepStatus::Skipped, Some(reason)),
+            Err(e) => {
+                log::error!("Step {} failed: {}", step, e);
+                self.push(step, StepStatus::Failed, Some(e.to_string()));
+            }
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "{}",
+            style(format!(
+                "Schemamap.io SDK init summary for DB {}:",
+                self.dbname
+            ))
+            .bold()
+        );
+        for step in &self.steps {
+            let (icon, name) = match step.status {
+                StepStatus::Succeeded => (CHECK, style(step.step).green()),
+                StepStatus::Skipped => (SKIP, style(step.step).dim()),
+                StepStatus::Failed => (CROSS, style(step.step).red().bold()),
+            };
+            match &step.detail {
+                Some(detail) => println!("  {}{:<18} {}", icon, name, detail),
+                None => println!("  {}{}", icon, name),
+            }
+        }
+    }
+}
+
+/// Runs the statements of a step atomically, rolling back on the first error.
+pub(crate) async fn batch_in_transaction(c: &Client, sql: &str) -> Result<()> {
+    c.batch_execute("begin").await?;
+    if let Err(e) = c.batch_execute(sql).await {
+        c.batch_execute("rollback").await?;
+        return Err(e.into());
+    }
+    c.batch_execute("commit").await?;
+    Ok(())
+}
+
+pub(crate) fn initialize_pgconfig(cli: &Cli, interactive: bool) -> PgConfig {
+    let pgconfig = parsers::parse_pgconfig_from_cli(cli)
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to discover PG connection: {}", e);
                 if interactive {
-                    prompt_for_pg_connstring()
-                        .parse::<tokio_postgres::Config>()
+                    parsers::parse_conn_str(&prompt_for_pg_connstring())
                         .unwrap()
                 } else {
                     log::error!("No PG connection string provided, please provide a connection string via --conn or DATABASE_URL environment variable");
@@ -64,11 +210,24 @@ pub(crate) fn initialize_pgconfig(cli: &Cli, interactive: bool) -> tokio_postgre
 
     log::debug!("Using connection string: {:?}", pgconfig);
 
-    return pgconfig;
+    pgconfig
+}
+
+/// A hot standby accepts the connection but fails on the first write, so bail out before changing anything.
+pub async fn ensure_primary(client: &Option<Client>) -> Result<()> {
+    if let Some(c) = client {
+        let in_recovery: bool = c.query_one("select pg_is_in_recovery()", &[]).await?.get(0);
+        if in_recovery {
+            return Err(anyhow::anyhow!(
+                "Connected to a read-only replica, point schemamap at the primary or list it in a multi-host connection string"
+            ));
+        }
+    }
+    Ok(())
 }
 
-pub async fn set_search_path(client: &Option<Client>) -> Result<()> {
-    let set_search_path_sql = "SET search_path TO schemamap;";
+pub async fn set_search_path(schema: &str, client: &Option<Client>) -> Result<()> {
+    let set_search_path_sql = format!("SET search_path TO {};", schema);
     if let Some(c) = client {
         c.simple_query(&set_search_path_sql).await?;
     } else {
@@ -77,24 +236,37 @@ pub async fn set_search_path(client: &Option<Client>) -> Result<()> {
     Ok(())
 }
 
-pub async fn create_schemamap_users(dbname: &String, client: &Option<Client>) -> Result<()> {
+pub async fn create_schemamap_users(
+    dbname: &String,
+    pgconfig: &PgConfig,
+    password_args: &PasswordArgs,
+    credentials_file: Option<&PathBuf>,
+    client: &Option<Client>,
+) -> Result<Outcome> {
     if let Some(c) = client {
-        log::info!("Creating Schemamap.io users in {}", dbname);
+        // Roles are cluster-wide, so another DB's init may have created them already
+        let exists: bool = c
+            .query_one(
+                "select exists(select 1 from pg_roles where rolname = 'schemamap')",
+                &[],
+            )
+            .await?
+            .get(0);
+        if exists {
+            log::info!("Skipping schemamap users creation, they already exist.");
+            return Ok(Outcome::Skipped("roles already exist".to_string()));
+        }
+    }
 
-        let _ = c
-            .batch_execute(CREATE_SCHEMAMAP_USERS_SQL)
-            .await
-            .inspect_err(|e| {
-                if e.to_string().contains("already exists") {
-                    log::info!("Skipping schemamap users creation, they already exist.");
-                } else {
-                    log::warn!("Failed to create schemamap users: {}", e);
-                }
-            });
-    } else {
-        println!("{}", CREATE_SCHEMAMAP_USERS_SQL);
+    let passwords = password_args.resolve(&SCHEMAMAP_ROLES, pgconfig, credentials_file, true)?;
+    let users_sql = credentials::render_users_sql(CREATE_SCHEMAMAP_USERS_SQL, &passwords);
+    if client.is_some() {
+        log::info!("Creating Schemamap.io users in {}", dbname);
     }
-    Ok(())
+    let saved =
+        credentials::apply_passwords(client, &users_sql, pgconfig, &passwords, credentials_file)
+            .await?;
+    Ok(Outcome::Done(Some(saved)))
 }
 
 async fn grant_create_connect(dbname: &str, client: &Option<Client>) -> Result<()> {
@@ -108,16 +280,26 @@ async fn grant_create_connect(dbname: &str, client: &Option<Client>) -> Result<(
     Ok(())
 }
 
-pub async fn create_schemamap_schema(client: &Option<Client>) -> Result<()> {
+/// Applies the SDK migrations the DB doesn't have yet.
+pub async fn create_schemamap_schema(schema: &str, client: &Option<Client>) -> Result<Outcome> {
     if let Some(c) = client {
-        let _ = c
-            .batch_execute(CREATE_SCHEMAMAP_SCHEMA_SQL)
-            .await
-            .inspect_err(|e| log::warn!("Failed to create schemamap schema: {}", e));
+        let report = migrations::migrate(c, schema).await?;
+        report.log();
+        if report.applied.is_empty() {
+            return Ok(Outcome::Skipped(format!(
+                "already at V{}",
+                migrations::bundled_version()
+            )));
+        }
+        return Ok(Outcome::Done(Some(format!(
+            "V{}, applied {} migration(s)",
+            migrations::bundled_version(),
+            report.applied.len()
+        ))));
     } else {
-        println!("{}", CREATE_SCHEMAMAP_SCHEMA_SQL);
+        migrations::print_migrations(schema);
     }
-    Ok(())
+    Ok(Outcome::Done(None))
 }
 
 fn remove_after_dot(s: &str) -> &str {
@@ -127,68 +309,140 @@ fn remove_after_dot(s: &str) -> &str {
     }
 }
 
-fn normalize_username<'a>(pgconfig: &'a Config, username: &'a str) -> &'a str {
+pub(crate) fn normalize_username<'a>(pgconfig: &'a Config, username: &'a str) -> &'a str {
     // NOTE: Supabase uses a $user.$dbid to dispatch to the the correct DB
-    if let Some(first_host) = pgconfig.get_hosts().get(0) {
-        if let tokio_postgres::config::Host::Tcp(host_str) = first_host {
-            if host_str.contains("supabase.com") {
-                return remove_after_dot(username);
-            }
+    if let Some(tokio_postgres::config::Host::Tcp(host_str)) = pgconfig.get_hosts().first() {
+        if host_str.contains("supabase.com") {
+            return remove_after_dot(username);
         }
     }
     username
 }
 
-pub async fn grant_schemamap_usage(pgconfig: &Config, client: &Option<Client>) -> Result<()> {
+pub async fn grant_schemamap_usage(
+    pgconfig: &Config,
+    schema: &str,
+    client: &Option<Client>,
+) -> Result<()> {
     // NOTE: without this Supabase via Supavisor/PGBouncer disconnects on CURRENT_USER
     let current_user = normalize_username(pgconfig, pgconfig.get_user().unwrap_or("postgres"));
 
-    let current_user_replaced_sql = GRANT_SCHEMAMAP_USAGE_SQL
+    let current_user_replaced_sql = in_schema(GRANT_SCHEMAMAP_USAGE_SQL, schema)
         .replace(" CURRENT_USER;", format!(" \"{}\";", current_user).as_str());
 
     if let Some(c) = client {
-        let _ = c
-            .batch_execute(&current_user_replaced_sql)
-            .await
-            .inspect_err(|e| log::warn!("Failed to grant schemamap usage: {}", e));
+        batch_in_transaction(c, &current_user_replaced_sql).await?;
     } else {
         println!("{}", current_user_replaced_sql);
     }
     Ok(())
 }
 
-pub async fn install_dev_extensions(pgconfig: &Config, client: &Option<Client>) -> Result<()> {
+pub async fn configure_application_roles(
+    pgconfig: &Config,
+    schema: &str,
+    roles: &[String],
+    client: &Option<Client>,
+) -> Result<()> {
+    // NOTE: same as in `grant_schemamap_usage`, for Supabase via Supavisor/PGBouncer
+    let current_user = normalize_username(pgconfig, pgconfig.get_user().unwrap_or("postgres"));
+
+    // Role names are validated, like `valid-pg-role-name?` of the JVM SDK
+    let grant_sql = std::iter::once(REVOKE_PUBLIC_SQL.to_string())
+        .chain(roles.iter().map(|role| {
+            log::info!(
+                "Granting {} schema usage permissions to role: {}",
+                schema,
+                role
+            );
+            GRANT_SCHEMAMAP_APP_ROLE_SQL.replace(":\"app_role\"", role)
+        }))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let grant_sql = in_schema(&grant_sql, schema)
+        .replace(" CURRENT_USER;", format!(" \"{}\";", current_user).as_str());
+
+    if let Some(c) = client {
+        batch_in_transaction(c, &grant_sql).await?;
+    } else {
+        println!("{}", grant_sql);
+    }
+    Ok(())
+}
+
+pub async fn update_i18n(
+    i18n_file: &std::path::Path,
+    schema: &str,
+    client: &Option<Client>,
+) -> Result<()> {
+    let i18n: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(i18n_file)?)
+        .map_err(|e| anyhow::anyhow!("Invalid i18n JSON in {}: {}", i18n_file.display(), e))?;
+
+    log::info!(
+        "Overriding {}.i18n() value from {}",
+        schema,
+        i18n_file.display()
+    );
+
+    if let Some(c) = client {
+        c.execute(&format!("select {}.update_i18n($1)", schema), &[&i18n])
+            .await?;
+    } else {
+        println!(
+            "select {}.update_i18n('{}'::jsonb);",
+            schema,
+            i18n.to_string().replace('\'', "''")
+        );
+    }
+    Ok(())
+}
+
+pub async fn install_dev_extensions(
+    pgconfig: &PgConfig,
+    dev_db: &str,
+    client: &Option<Client>,
+) -> Result<()> {
     // Have to submit separately otherwise the commands run in a transaction context
     // which is not allowed for CREATE DATABASE.
 
-    log::info!("Creating \"{}\" DB", SCHEMAMAP_DEV_DB);
-    let create_db_sql = format!("CREATE DATABASE {};", SCHEMAMAP_DEV_DB);
+    // The dev SQL refers to its own DB by name, the name is validated in Settings
+    let dev_sql = SCHEMAMAP_DEV_SQL.replace(SCHEMAMAP_DEV_DB, dev_db);
+
+    log::info!("Creating \"{}\" DB", dev_db);
+    let create_db_sql = format!("CREATE DATABASE {};", dev_db);
     if let Some(c) = client {
-        if let Err(e) = c.execute(&create_db_sql, &[]).await {
-            log::warn!("Failed to create \"{}\" database: {}", SCHEMAMAP_DEV_DB, e);
-            return Ok(());
-        } else {
-            let mut dev_pgconfig = pgconfig.clone();
-            let dev_pgconfig_dbname = dev_pgconfig.dbname(&SCHEMAMAP_DEV_DB);
-
-            let (schemamp_dev_c, connection) = dev_pgconfig_dbname.connect(NoTls).await?;
-
-            tokio::spawn(async move {
-                if let Err(e) = connection.await {
-                    log::warn!("Connection error to \"schemamap_dev\" DB: {}", e);
-                }
-            });
+        c.execute(&create_db_sql, &[])
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create \"{}\" database: {}", dev_db, e))?;
+
+        let mut dev_pgconfig = pgconfig.clone();
+        dev_pgconfig.dbname(dev_db);
 
-            if let Err(e) = schemamp_dev_c.batch_execute(SCHEMAMAP_DEV_SQL).await {
-                log::warn!("Failed to install dev extensions: {}", e);
-            } else {
-                log::info!("Installed dev extensions to \"{}\" DB", SCHEMAMAP_DEV_DB);
+        let (schemamp_dev_c, connection) = dev_pgconfig
+            .connect(dev_pgconfig.tls.make_connector()?)
+            .await?;
+
+        let dev_db_name = dev_db.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::warn!("Connection error to \"{}\" DB: {}", dev_db_name, e);
             }
-        }
+        });
+
+        batch_in_transaction(&schemamp_dev_c, &dev_sql)
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to install dev extensions: {}, drop the \"{}\" DB before retrying",
+                    e,
+                    dev_db
+                )
+            })?;
+        log::info!("Installed dev extensions to \"{}\" DB", dev_db);
     } else {
         println!("{}", create_db_sql);
-        println!("\\c schemamap_dev"); // assuming psql
-        println!("{}", SCHEMAMAP_DEV_SQL);
+        println!("\\c {}", dev_db); // assuming psql
+        println!("{}", dev_sql);
     }
 
     Ok(())
@@ -202,59 +456,132 @@ pub async fn init(cli: &Cli, args: &InitArgs) -> Result<()> {
     // No reason to prompt for input if not interactive/TTY
     let interactive = atty::is(atty::Stream::Stdout) && args.input.unwrap_or(true);
 
-    let pgconfig = initialize_pgconfig(cli, interactive);
+    let settings = Settings::resolve(cli)?;
+    let dev_db = settings.dev_dbname.value.clone();
+
+    let app_roles = if args.app_roles.is_empty() {
+        settings.application_roles.as_ref().map(|r| r.value.clone())
+    } else {
+        if let Some(invalid) = args.app_roles.iter().find(|r| !valid_pg_identifier(r)) {
+            return Err(anyhow::anyhow!(
+                "Invalid application role name \"{}\" from --app-role",
+                invalid
+            ));
+        }
+        Some(args.app_roles.clone())
+    };
+
+    let mut pgconfig = initialize_pgconfig(cli, interactive);
+    let json = args.json.unwrap_or(false);
+
+    let mut summary = InitSummary {
+        dbname: pgconfig.get_dbname().unwrap_or("postgres").to_string(),
+        ..Default::default()
+    };
 
     // Start by establishing a Postgres admin connection to DB
-    let (client, connection) = if dry_run {
-        (None, None)
+    let client = if dry_run {
+        None
     } else {
-        match pgconfig.connect(NoTls).await {
-            Ok(conn) => (Some(conn.0), Some(conn.1)),
+        let prompt = interactive && !json && atty::is(atty::Stream::Stdin);
+        match porcelain::connect_with_prompt(&mut pgconfig, prompt).await {
+            Ok(client) => {
+                summary.push("connect", StepStatus::Succeeded, None);
+                Some(client)
+            }
             Err(e) => {
-                log::error!("Postgres connection error: {}", e);
-                log::error!("{:?}", pgconfig);
-
-                println!("Alternatively, try running:\n");
-                println!("schemamap init --dbname=postgres --dry-run | psql");
-                std::process::exit(1);
+                summary.push("connect", StepStatus::Failed, Some(e.to_string()));
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&summary)?);
+                } else {
+                    println!("Alternatively, try running:\n");
+                    println!("schemamap init --dbname=postgres --dry-run | psql");
+                }
+                return Err(anyhow::anyhow!("Schemamap.io Postgres SDK init failed"));
             }
         }
     };
 
-    connection.map(|c| {
-        tokio::spawn(async move {
-            if let Err(e) = c.await {
-                log::error!("Postgres connection error: {}", e);
-            }
-        })
-    });
+    let dbname = summary.dbname.clone();
 
-    let dbname = pgconfig.get_dbname().unwrap_or("postgres").to_string();
+    let schema = settings.schema.value.clone();
+    set_search_path(&schema, &client).await?;
 
     log::info!("Installing Schemamap.io Postgres SDK to DB: {}", dbname);
 
-    create_schemamap_users(&dbname, &client).await?;
-
-    set_search_path(&client).await?;
+    summary.step("check-primary", ensure_primary(&client)).await;
+
+    let credentials_file = args.passwords.credentials_file(&settings);
+    summary
+        .step(
+            "create-users",
+            create_schemamap_users(
+                &dbname,
+                &pgconfig,
+                &args.passwords,
+                credentials_file.as_ref(),
+                &client,
+            ),
+        )
+        .await;
 
-    grant_create_connect(&dbname, &client).await?;
+    summary
+        .step("grant-database", grant_create_connect(&dbname, &client))
+        .await;
 
-    log::info!("Creating \"schemamap\" SDK schema in {}", dbname);
+    log::info!("Creating \"{}\" SDK schema in {}", schema, dbname);
 
-    create_schemamap_schema(&client).await?;
+    summary
+        .step("migrate-schema", create_schemamap_schema(&schema, &client))
+        .await;
 
     log::info!(
-        "Granting usage rights of \"schemamap\" schema in {}",
+        "Granting usage rights of \"{}\" schema in {}",
+        schema,
         dbname
     );
 
-    grant_schemamap_usage(&pgconfig, &client).await?;
+    summary
+        .step(
+            "grant-usage",
+            grant_schemamap_usage(&pgconfig, &schema, &client),
+        )
+        .await;
+
+    match &app_roles {
+        Some(roles) => {
+            summary
+                .step(
+                    "application-roles",
+                    configure_application_roles(&pgconfig, &schema, roles, &client),
+                )
+                .await
+        }
+        None => {
+            log::warn!("No application roles configured, only the schemamap roles can use the SDK. Grant it to yours with --app-role");
+            summary.skip("application-roles", "none configured, see --app-role")
+        }
+    }
+
+    match &settings.i18n_file {
+        Some(i18n_file) => {
+            summary
+                .step("i18n", update_i18n(&i18n_file.value, &schema, &client))
+                .await
+        }
+        None => summary.skip("i18n", "no i18n file configured"),
+    }
+
+    if summary.failed() {
+        summary.skip("dev-extensions", "an earlier step failed");
+        return report_summary(&summary, json, dry_run);
+    }
 
     log::info!("Schemamap.io Postgres SDK installed successfully");
     let dev_db_exists: bool = if let Some(c) = &client {
         c.query_one(
             "SELECT exists(select 1 from pg_database where datname = $1)",
-            &[&SCHEMAMAP_DEV_DB],
+            &[&dev_db],
         )
         .await?
         .get::<_, bool>(0)
@@ -262,17 +589,41 @@ pub async fn init(cli: &Cli, args: &InitArgs) -> Result<()> {
         false
     };
 
-    let install_dev;
-    if !dev_db_exists && interactive && args.dev.is_none() && !dry_run {
-        install_dev = prompt_for_dev_installation();
+    let install_dev = if !dev_db_exists && interactive && !json && args.dev.is_none() && !dry_run {
+        prompt_for_dev_installation()
     } else {
-        install_dev = args.dev.unwrap_or(false);
-    }
+        args.dev.unwrap_or(false)
+    };
 
-    if !dev_db_exists && install_dev {
-        install_dev_extensions(&pgconfig, &client).await?;
+    if dev_db_exists {
+        summary.skip(
+            "dev-extensions",
+            &format!("\"{}\" DB already exists", dev_db),
+        );
+    } else if install_dev {
+        summary
+            .step(
+                "dev-extensions",
+                install_dev_extensions(&pgconfig, &dev_db, &client),
+            )
+            .await;
+    } else {
+        summary.skip("dev-extensions", "not requested");
     }
 
+    report_summary(&summary, json, dry_run)
+}
+
+// The dry-run output is SQL for psql, so it gets no summary
+fn report_summary(summary: &InitSummary, json: bool, dry_run: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(summary)?);
+    } else if !dry_run {
+        summary.print();
+    }
+    if summary.failed() {
+        return Err(anyhow::anyhow!("Schemamap.io Postgres SDK init failed"));
+    }
     Ok(())
 }
 
@@ -281,11 +632,11 @@ fn theme() -> ColorfulTheme {
 }
 
 fn prompt_for_dev_installation() -> bool {
-    return dialoguer::Confirm::with_theme(&theme())
+    dialoguer::Confirm::with_theme(&theme())
         .with_prompt("Do you want to install development-time extensions for DB snapshotting?")
         .default(true)
         .interact()
-        .unwrap_or(false);
+        .unwrap_or(false)
 }
 
 fn prompt_for_pg_connstring() -> String {
@@ -1,8 +1,16 @@
 mod common;
+mod config;
+mod connections;
+mod credentials;
 mod doctor;
+mod fingerprint;
+mod hooks;
 mod init;
+mod migrations;
 mod parsers;
 pub mod porcelain;
+mod tls;
+mod uninstall;
 mod up;
 
 use anyhow::Result;
@@ -36,11 +44,13 @@ fn configure_logging(debug: bool) {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     // In case of dry-run, we don't want to log at all, to not interfere with STDOUT/STDERR
     let dry_run = match cli.command {
         Commands::Init(ref args) => args.dry_run.unwrap_or(false),
+        Commands::Uninstall(ref args) => args.dry_run.unwrap_or(false),
+        Commands::RotatePasswords(ref args) => args.dry_run.unwrap_or(false),
         _ => false,
     };
 
@@ -50,14 +60,22 @@ async fn main() -> Result<()> {
         configure_logging(cli.verbose > 0);
     }
 
+    cli.project = config::ProjectConfig::discover()?;
+
     match cli.command {
         Commands::Init(ref args) => init::init(&cli, args).await,
-        Commands::Up(args) => up::up(args).await,
+        Commands::Upgrade(ref args) => migrations::upgrade(&cli, args).await,
+        Commands::Uninstall(ref args) => uninstall::uninstall(&cli, args).await,
+        Commands::RotatePasswords(ref args) => credentials::rotate_passwords(&cli, args).await,
+        Commands::Up(ref args) => up::up(&cli, args).await,
         Commands::Doctor(ref args) => doctor::doctor(&cli, args).await,
+        Commands::Connections(ref args) => connections::connections(&cli, args).await,
+        Commands::Config(ref args) => config::config(&cli, args).await,
         Commands::Status(ref args) => porcelain::status(&cli, args).await,
         Commands::Refresh(_) => porcelain::refresh(&cli).await,
         Commands::Snapshot(ref args) => porcelain::snapshot(&cli, args).await,
         Commands::Restore(ref args) => porcelain::restore(&cli, args).await,
         Commands::List(ref args) => porcelain::list(&cli, args).await,
+        Commands::Hooks(ref args) => hooks::hooks(&cli, args).await,
     }
 }
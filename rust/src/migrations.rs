@@ -0,0 +1,485 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use clap::Args;
+use tokio_postgres::Client;
+
+use crate::common::{Cli, SCHEMAMAP_SCHEMA};
+use crate::config::Settings;
+use crate::{init, parsers, porcelain};
+
+/// One of the `sql/V0000NN__*.sql` SDK migrations, shared with the JVM SDK.
+pub(crate) struct Migration {
+    pub(crate) script: &'static str,
+    pub(crate) sql: &'static str,
+}
+
+macro_rules! migration {
+    ($script:literal) => {
+        Migration {
+            script: $script,
+            sql: include_str!(concat!("../sql/", $script)),
+        }
+    };
+}
+
+pub(crate) const MIGRATIONS: [Migration; 13] = [
+    migration!("V000001__init.sql"),
+    migration!("V000002__data_migrations.sql"),
+    migration!("V000003__developer_experience_improvements.sql"),
+    migration!("V000004__track_column_order_in_schema_metadata_overview.sql"),
+    migration!("V000005__track_sequences_for_pk_constraints.sql"),
+    migration!("V000006__ignore_all_schemas_without_usage.sql"),
+    migration!("V000007__drop_table_metadata.sql"),
+    migration!("V000008__add_count_estimate_helper_fn.sql"),
+    migration!("V000009__track_index_definitions_in_smo.sql"),
+    migration!("V000010__schema_concepts.sql"),
+    migration!("V000011__status_fn.sql"),
+    migration!("V000012__more_concepts.sql"),
+    migration!("V000013__drop_smo_view.sql"),
+];
+
+// Installs made by earlier CLI versions ran the migrations of their release at once from create_schemamap_schema.sql.
+// The version is that of the newest migration whose objects are there, V000002 being the oldest with data_migrations.
+const LEGACY_VERSION_PROBES: [(&str, &str); 11] = [
+    (
+        "000013",
+        "to_regprocedure('schemamap.refresh()') is not null and to_regclass('schemamap.smo') is null",
+    ),
+    ("000012", "to_regprocedure('schemamap.refresh()') is not null"),
+    ("000011", "to_regclass('schemamap.status') is not null"),
+    ("000010", "to_regprocedure('schemamap.list_concepts()') is not null"),
+    (
+        "000009",
+        "pg_get_viewdef(to_regclass('schemamap.schema_metadata_overview')) like '%pg_get_indexdef%'",
+    ),
+    ("000008", "to_regprocedure('schemamap.count_estimate(text)') is not null"),
+    ("000007", "to_regclass('schemamap.table_metadata') is null"),
+    (
+        "000006",
+        "pg_get_viewdef(to_regclass('schemamap.schema_metadata_overview')) like '%has_schema_privilege%'",
+    ),
+    (
+        "000005",
+        "pg_get_viewdef(to_regclass('schemamap.schema_metadata_overview')) like '%pg_get_serial_sequence%'",
+    ),
+    (
+        "000004",
+        "exists (select 1 from pg_attribute where attrelid = to_regclass('schemamap.schema_metadata_overview') and attname = 'attnum' and not attisdropped)",
+    ),
+    (
+        "000003",
+        "to_regprocedure('schemamap.update_function_definition()') is not null",
+    ),
+];
+const LEGACY_MIN_VERSION: &str = "000002";
+
+// The JVM SDK migrates with Flyway, so its history table doubles as the version table
+const CREATE_HISTORY_TABLE_SQL: &str = "create schema if not exists schemamap;
+
+create table if not exists schemamap.flyway_schema_history (
+  installed_rank int not null constraint flyway_schema_history_pk primary key,
+  version varchar(50),
+  description varchar(200) not null,
+  type varchar(20) not null,
+  script varchar(1000) not null,
+  checksum int,
+  installed_by varchar(100) not null,
+  installed_on timestamp not null default now(),
+  execution_time int not null,
+  success boolean not null
+);
+
+create index if not exists flyway_schema_history_s_idx on schemamap.flyway_schema_history (success);";
+
+const INSERT_HISTORY_SQL: &str = "insert into schemamap.flyway_schema_history
+(installed_rank, version, description, type, script, checksum, installed_by, execution_time, success)
+select coalesce(max(installed_rank), 0) + 1, $1, $2, $3, $4, $5, current_user, $6, true
+from schemamap.flyway_schema_history";
+
+impl Migration {
+    /// `000002` of `V000002__data_migrations.sql`
+    pub(crate) fn version(&self) -> &'static str {
+        let name = self.script.strip_prefix('V').unwrap_or(self.script);
+        name.split_once("__").map_or(name, |(version, _)| version)
+    }
+
+    /// `data migrations` of `V000002__data_migrations.sql`, like Flyway
+    fn description(&self) -> String {
+        let name = self.script.strip_suffix(".sql").unwrap_or(self.script);
+        name.split_once("__")
+            .map_or("", |(_, description)| description)
+            .replace('_', " ")
+    }
+
+    /// Same as Flyway's: CRC32 of the lines without line breaks and BOM.
+    fn checksum(&self) -> i32 {
+        let sql = self.sql.strip_prefix('\u{feff}').unwrap_or(self.sql);
+        sql.lines().fold(0, |crc, line| crc32(crc, line.as_bytes())) as i32
+    }
+}
+
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Moves SQL written for the `schemamap` schema to another one.
+/// The `schemamap` role keeps its name, so only qualified names, `schema schemamap`,
+/// `exists schemamap` and `'schemamap'` literals are rewritten.
+pub(crate) fn in_schema(sql: &str, schema: &str) -> String {
+    if schema == SCHEMAMAP_SCHEMA {
+        return sql.to_string();
+    }
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut last = 0;
+    for (i, _) in sql.match_indices(SCHEMAMAP_SCHEMA) {
+        let before = &sql[..i];
+        let after = &sql[i + SCHEMAMAP_SCHEMA.len()..];
+        let prev = before.chars().next_back();
+        let next = after.chars().next();
+        if prev.is_some_and(is_ident_char) || next.is_some_and(is_ident_char) {
+            continue;
+        }
+        let preceded_by = |keyword: &str| {
+            before
+                .get(before.len().saturating_sub(keyword.len())..)
+                .is_some_and(|b| b.eq_ignore_ascii_case(keyword))
+        };
+        let is_schema = next == Some('.')
+            || (prev == Some('\'') && next == Some('\''))
+            || preceded_by("schema ")
+            || preceded_by("exists ");
+        if is_schema {
+            rewritten.push_str(&sql[last..i]);
+            rewritten.push_str(schema);
+            last = i + SCHEMAMAP_SCHEMA.len();
+        }
+    }
+    rewritten.push_str(&sql[last..]);
+    rewritten
+}
+
+pub(crate) fn bundled_version() -> &'static str {
+    MIGRATIONS.last().map_or("", |m| m.version())
+}
+
+/// Installed and bundled SDK versions, along with the migrations applied to get from one to the other.
+pub(crate) struct MigrationReport {
+    pub(crate) installed: Option<String>,
+    pub(crate) applied: Vec<&'static str>,
+}
+
+impl MigrationReport {
+    pub(crate) fn log(&self) {
+        match (&self.installed, self.applied.is_empty()) {
+            (None, true) => log::info!("Schemamap.io SDK not installed"),
+            (Some(installed), true) => log::info!(
+                "Schemamap.io SDK is up to date, installed version: V{}, bundled version: V{}",
+                installed,
+                bundled_version()
+            ),
+            (installed, false) => {
+                log::info!(
+                    "Upgraded Schemamap.io SDK from {} to V{}, applied {} migration(s)",
+                    installed
+                        .as_ref()
+                        .map_or("nothing".to_string(), |v| format!("V{}", v)),
+                    bundled_version(),
+                    self.applied.len()
+                );
+            }
+        }
+    }
+}
+
+/// The latest applied version, `None` if the SDK isn't installed.
+pub(crate) async fn installed_version(client: &Client, schema: &str) -> Result<Option<String>> {
+    let has_history: bool = client
+        .query_one(
+            "select to_regclass($1 || '.flyway_schema_history') is not null",
+            &[&schema],
+        )
+        .await?
+        .get(0);
+    if has_history {
+        // Versions are text, so the last applied one rather than the text max
+        let version: Option<String> = client
+            .query_opt(
+                &in_schema(
+                    "select version from schemamap.flyway_schema_history where success and version is not null order by installed_rank desc limit 1",
+                    schema,
+                ),
+                &[],
+            )
+            .await?
+            .map(|row| row.get(0));
+        return Ok(version);
+    }
+
+    let legacy_install: bool = client
+        .query_one(
+            "select to_regclass($1 || '.data_migrations') is not null",
+            &[&schema],
+        )
+        .await?
+        .get(0);
+    if !legacy_install {
+        return Ok(None);
+    }
+    for (version, probe) in LEGACY_VERSION_PROBES {
+        let found: Option<bool> = client
+            .query_one(&in_schema(&format!("select {}", probe), schema), &[])
+            .await?
+            .get(0);
+        if found == Some(true) {
+            return Ok(Some(version.to_string()));
+        }
+    }
+    Ok(Some(LEGACY_MIN_VERSION.to_string()))
+}
+
+// Checksum mismatches mean a migration changed after it was applied, which only ever happens in development
+async fn check_applied(client: &Client, schema: &str) -> Result<()> {
+    let rows = client
+        .query(
+            &in_schema(
+                "select script, checksum, success from schemamap.flyway_schema_history where type = 'SQL'",
+                schema,
+            ),
+            &[],
+        )
+        .await?;
+    for row in rows {
+        let script: String = row.get(0);
+        let checksum: Option<i32> = row.get(1);
+        let success: bool = row.get(2);
+        if !success {
+            return Err(anyhow::anyhow!(
+                "Migration {} failed earlier, fix it in {}.flyway_schema_history before upgrading",
+                script,
+                schema
+            ));
+        }
+        match MIGRATIONS.iter().find(|m| m.script == script) {
+            Some(migration) if checksum != Some(migration.checksum()) => {
+                log::warn!("Migration {} changed since it was applied", script)
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+async fn apply_pending(client: &Client, schema: &str) -> Result<MigrationReport> {
+    // Concurrent `init`s wait for each other instead of applying the same migrations twice
+    client
+        .batch_execute(&in_schema(
+            "select pg_advisory_xact_lock(hashtext('schemamap.flyway_schema_history'))",
+            schema,
+        ))
+        .await?;
+
+    let installed = installed_version(client, schema).await?;
+    client
+        .batch_execute(&in_schema(CREATE_HISTORY_TABLE_SQL, schema))
+        .await?;
+
+    let insert_history_sql = in_schema(INSERT_HISTORY_SQL, schema);
+    let has_rows: bool = client
+        .query_one(
+            &in_schema(
+                "select exists(select 1 from schemamap.flyway_schema_history)",
+                schema,
+            ),
+            &[],
+        )
+        .await?
+        .get(0);
+    if let (Some(baseline), false) = (&installed, has_rows) {
+        log::info!(
+            "Baselining Schemamap.io SDK installed by an earlier CLI at V{}",
+            baseline
+        );
+        client
+            .execute(
+                &insert_history_sql,
+                &[
+                    baseline,
+                    &"<< Flyway Baseline >>",
+                    &"BASELINE",
+                    &"<< Flyway Baseline >>",
+                    &None::<i32>,
+                    &0,
+                ],
+            )
+            .await?;
+    }
+    check_applied(client, schema).await?;
+
+    if let Some(installed) = &installed {
+        if installed.as_str() > bundled_version() {
+            log::warn!(
+                "Installed Schemamap.io SDK V{} is newer than the bundled V{}, upgrade the CLI",
+                installed,
+                bundled_version()
+            );
+        }
+    }
+
+    let mut applied = vec![];
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| installed.as_deref().is_none_or(|v| m.version() > v))
+    {
+        log::info!("Applying {}", migration.script);
+        let started = Instant::now();
+        client
+            .batch_execute(&in_schema(migration.sql, schema))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to apply {}: {}", migration.script, e))?;
+        client
+            .execute(
+                &insert_history_sql,
+                &[
+                    &migration.version(),
+                    &migration.description(),
+                    &"SQL",
+                    &migration.script,
+                    &Some(migration.checksum()),
+                    &(started.elapsed().as_millis() as i32),
+                ],
+            )
+            .await?;
+        applied.push(migration.script);
+    }
+
+    Ok(MigrationReport { installed, applied })
+}
+
+/// Applies the pending SDK migrations in one transaction, so a failure leaves the DB as it was.
+pub(crate) async fn migrate(client: &Client, schema: &str) -> Result<MigrationReport> {
+    client
+        .batch_execute(&format!("begin; set local search_path to {}", schema))
+        .await?;
+    match apply_pending(client, schema).await {
+        Ok(report) => {
+            client.batch_execute("commit").await?;
+            Ok(report)
+        }
+        Err(e) => {
+            client.batch_execute("rollback").await?;
+            Err(e)
+        }
+    }
+}
+
+/// The SQL of all migrations along with their history rows, for piping into psql.
+pub(crate) fn print_migrations(schema: &str) {
+    println!("{}", in_schema(CREATE_HISTORY_TABLE_SQL, schema));
+    for migration in &MIGRATIONS {
+        println!("\n-- {}\nbegin;", migration.script);
+        println!("{}", in_schema(migration.sql, schema));
+        let history_sql = in_schema(INSERT_HISTORY_SQL, schema)
+            .replace("$1", &format!("'{}'", migration.version()))
+            .replace("$2", &format!("'{}'", migration.description()))
+            .replace("$3", "'SQL'")
+            .replace("$4", &format!("'{}'", migration.script))
+            .replace("$5", &migration.checksum().to_string())
+            .replace("$6", "0");
+        println!("{};\ncommit;", history_sql);
+    }
+}
+
+#[derive(Args)]
+pub struct UpgradeArgs {}
+
+pub async fn upgrade(cli: &Cli, _args: &UpgradeArgs) -> Result<()> {
+    let schema = Settings::resolve(cli)?.schema.value;
+    let mut pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    let client = Some(porcelain::connect_from_config(&mut pgconfig).await?);
+
+    init::ensure_primary(&client).await?;
+
+    if let Some(c) = &client {
+        if installed_version(c, &schema).await?.is_none() {
+            return Err(anyhow::anyhow!(
+                "Schemamap.io SDK is not installed in the {} schema of this DB, run `schemamap init` first",
+                schema
+            ));
+        }
+    }
+
+    init::create_schemamap_schema(&schema, &client).await?;
+
+    // Objects of new migrations need the same ownership and grants as the rest
+    init::grant_schemamap_usage(&pgconfig, &schema, &client).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migration_metadata() {
+        let migration = &MIGRATIONS[1];
+        assert_eq!(migration.version(), "000002");
+        assert_eq!(migration.description(), "data migrations");
+        assert_eq!(bundled_version(), "000013");
+        assert!(MIGRATIONS
+            .windows(2)
+            .all(|w| w[0].version() < w[1].version()));
+    }
+
+    #[test]
+    fn test_legacy_version_probes() {
+        let versions: Vec<&str> = LEGACY_VERSION_PROBES.iter().map(|(v, _)| *v).collect();
+        assert!(versions.windows(2).all(|w| w[0] > w[1]));
+        assert!(versions
+            .iter()
+            .all(|v| *v > LEGACY_MIN_VERSION && MIGRATIONS.iter().any(|m| m.version() == *v)));
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        // Line breaks don't count, like in Flyway
+        let migration = Migration {
+            script: "V1__test.sql",
+            sql: "select 1;\r\nselect 2;\n",
+        };
+        assert_eq!(migration.checksum(), crc32(0, b"select 1;select 2;") as i32);
+    }
+
+    #[test]
+    fn test_in_schema() {
+        let sql = "create schema if not exists schemamap;
+grant usage on schema schemamap to schemamap;
+select schemamap.status() where nspname = 'schemamap' and rolname = schemamap_readonly;
+execute format('alter table schemamap.%I owner to schemamap', t);";
+        assert_eq!(
+            in_schema(sql, "_tools"),
+            "create schema if not exists _tools;
+grant usage on schema _tools to schemamap;
+select _tools.status() where nspname = '_tools' and rolname = schemamap_readonly;
+execute format('alter table _tools.%I owner to schemamap', t);"
+        );
+        assert_eq!(in_schema(sql, SCHEMAMAP_SCHEMA), sql);
+    }
+}
@@ -1,39 +1,171 @@
 use std::env;
+use std::path::{Path, PathBuf};
 
 use tokio_postgres::Config;
 
+use super::{ParamOrigin, PgConfig, Source};
+
+const DEFAULT_PORT: u16 = 5432;
+
+// Debian/Ubuntu and Fedora packages, then the compiled-in default of upstream builds and Homebrew
+const SOCKET_DIRS: [&str; 3] = ["/var/run/postgresql", "/run/postgresql", "/tmp"];
+
+fn socket_exists(dir: &Path, port: u16) -> bool {
+    dir.join(format!(".s.PGSQL.{}", port)).exists()
+}
+
+// Lines 4 and 5 of postmaster.pid hold the port and the first socket directory of a running server
+fn socket_from_pid_file(pgdata: &Path) -> Option<(PathBuf, u16)> {
+    let pid_file = std::fs::read_to_string(pgdata.join("postmaster.pid")).ok()?;
+    let mut lines = pid_file.lines().skip(3);
+    let port = lines.next()?.trim().parse().ok()?;
+    let dir = lines.next()?.trim();
+    if dir.is_empty() {
+        return None;
+    }
+    // Relative socket directories are relative to the data directory
+    let dir = pgdata.join(dir);
+    socket_exists(&dir, port).then_some((dir, port))
+}
+
+fn find_socket_in(
+    pgdata: Option<&Path>,
+    dirs: &[PathBuf],
+    port: Option<u16>,
+) -> Option<(PathBuf, u16)> {
+    if let Some((dir, found_port)) = pgdata.and_then(socket_from_pid_file) {
+        if port.is_none_or(|p| p == found_port) {
+            return Some((dir, found_port));
+        }
+    }
+    let port = port.unwrap_or(DEFAULT_PORT);
+    dirs.iter()
+        .find(|dir| socket_exists(dir, port))
+        .map(|dir| (dir.clone(), port))
+}
+
+/// The socket directory and port of a locally running server, from `$PGDATA` or the usual socket directories.
+pub(crate) fn find_socket(port: Option<u16>) -> Option<(PathBuf, u16)> {
+    if !cfg!(unix) {
+        return None;
+    }
+    let pgdata = env::var_os("PGDATA").map(PathBuf::from);
+    let dirs: Vec<PathBuf> = SOCKET_DIRS.iter().map(PathBuf::from).collect();
+    let socket = find_socket_in(pgdata.as_deref(), &dirs, port);
+    if let Some((dir, port)) = &socket {
+        log::debug!(
+            "Found Postgres Unix socket {}",
+            dir.join(format!(".s.PGSQL.{}", port)).display()
+        );
+    }
+    socket
+}
+
+/// The local server, with the parameters no other source knew about marked as guessed.
 pub(crate) fn get_pg_config(
     dbname: Option<String>,
     username: Option<String>,
     port: Option<u16>,
-) -> Config {
-    // Last resort, use default for default Postgres Docker image:
+) -> anyhow::Result<PgConfig> {
+    let mut guessed = vec![];
+
+    // Mimicking the default Postgres Docker image's env vars:
     // $ docker run --name some-postgres -e POSTGRES_PASSWORD=mysecretpassword -d postgres
-    let dbname =
-        dbname.unwrap_or_else(|| env::var("POSTGRES_DB").unwrap_or("postgres".to_string()));
+    let dbname = dbname
+        .or_else(|| env::var("POSTGRES_DB").ok())
+        .unwrap_or_else(|| {
+            guessed.push("dbname");
+            "postgres".to_string()
+        });
     let username = username
         .or_else(|| env::var("POSTGRES_USER").ok())
-        // Defaulting to $USER as it's a common pattern to have as a superuser locally
-        .or_else(|| env::var("USER").ok())
-        .unwrap_or("postgres".to_string());
-    let port = port.unwrap_or(5432);
+        .unwrap_or_else(|| {
+            guessed.push("user");
+            // Defaulting to $USER as it's a common pattern to have as a superuser locally
+            env::var("USER").unwrap_or("postgres".to_string())
+        });
+    // No made up password: peer and trust auth need none, and the others get prompted for one
     let password = env::var("POSTGRES_PASSWORD")
-        .unwrap_or_else(|_| env::var("PGPASSWORD").unwrap_or("postgres".to_string()));
+        .or_else(|_| env::var("PGPASSWORD"))
+        .ok();
 
-    let pghost = env::var("PGHOST").ok();
-    let host = pghost.clone().unwrap_or_else(|| "localhost".to_string());
+    let mut config = Config::new();
+    config.user(&username).dbname(&dbname);
+    if let Some(password) = password {
+        config.password(password);
+    }
 
-    let config_str = if pghost.is_some() {
-        format!(
-            "host={} user={} dbname={} password={}",
-            host, username, dbname, password
-        )
-    } else {
-        format!(
-            "host={} user={} dbname={} password={} port={}",
-            host, username, dbname, password, port,
-        )
+    let pghost = env::var("PGHOST").ok().filter(|h| !h.is_empty());
+    let socket = match &pghost {
+        Some(host) if host.starts_with('/') => {
+            Some((PathBuf::from(host), port.unwrap_or(DEFAULT_PORT)))
+        }
+        Some(_) => None,
+        None => find_socket(port),
     };
 
-    config_str.parse::<Config>().unwrap()
+    match socket {
+        #[cfg(unix)]
+        Some((dir, port)) => {
+            config.host_path(dir).port(port);
+        }
+        _ => {
+            if pghost.is_none() {
+                guessed.push("host");
+            }
+            if port.is_none() {
+                guessed.push("port");
+            }
+            config
+                .host(pghost.as_deref().unwrap_or("localhost"))
+                .port(port.unwrap_or(DEFAULT_PORT));
+        }
+    }
+
+    let mut config = PgConfig::from_config(config)?;
+    for param in guessed {
+        config.set_origin(param, ParamOrigin::Guessed(Source::Default));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_socket() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let pgdata = root.join("data");
+        let run_dir = root.join("run");
+        std::fs::create_dir_all(&pgdata).unwrap();
+        std::fs::create_dir_all(&run_dir).unwrap();
+        let dirs = vec![root.join("missing"), run_dir.clone()];
+
+        assert_eq!(find_socket_in(Some(&pgdata), &dirs, None), None);
+
+        std::fs::write(run_dir.join(".s.PGSQL.5432"), "").unwrap();
+        assert_eq!(
+            find_socket_in(Some(&pgdata), &dirs, None),
+            Some((run_dir.clone(), 5432))
+        );
+        assert_eq!(find_socket_in(None, &dirs, Some(5433)), None);
+
+        // A server started with unix_socket_directories = '.'
+        std::fs::write(
+            pgdata.join("postmaster.pid"),
+            format!("42\n{}\n1700000000\n5433\n.\n*\n", pgdata.display()),
+        )
+        .unwrap();
+        std::fs::write(pgdata.join(".s.PGSQL.5433"), "").unwrap();
+        assert_eq!(
+            find_socket_in(Some(&pgdata), &dirs, None),
+            Some((pgdata.join("."), 5433))
+        );
+        assert_eq!(
+            find_socket_in(Some(&pgdata), &dirs, Some(5432)),
+            Some((run_dir, 5432))
+        );
+    }
 }
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio_postgres::Config;
+
+use super::{split_top_level, unquote, PgConfig};
+
+// As per: https://docs.djangoproject.com/en/stable/ref/settings/#databases
+const SETTINGS_MODULE_ENV: &str = "DJANGO_SETTINGS_MODULE";
+
+/// The text between the bracket at `open` and its matching closing bracket.
+fn bracketed(s: &str, open: usize) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&s[open + 1..open + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+struct Call<'a> {
+    function: &'a str,
+    args: Vec<&'a str>,
+    kwargs: HashMap<&'a str, &'a str>,
+}
+
+fn parse_call(expr: &str) -> Option<Call<'_>> {
+    let expr = expr.trim();
+    let open = expr.find('(')?;
+    let body = bracketed(expr, open)?;
+    let mut call = Call {
+        function: expr[..open].trim(),
+        args: vec![],
+        kwargs: HashMap::new(),
+    };
+    for arg in split_top_level(body) {
+        match arg.split_once('=') {
+            Some((key, value)) if key.trim().chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                call.kwargs.insert(key.trim(), value.trim());
+            }
+            _ => call.args.push(arg.trim()),
+        }
+    }
+    Some(call)
+}
+
+/// Evaluates string literals and the env lookups of `os.environ`, `os.getenv`, django-environ and python-decouple.
+fn eval_python(expr: &str, env_map: &HashMap<String, String>) -> Option<String> {
+    let expr = expr.trim();
+    if let Some(s) = unquote(expr) {
+        return Some(s.to_string());
+    }
+    if let Ok(n) = expr.parse::<i64>() {
+        return Some(n.to_string());
+    }
+
+    if let Some(rest) = expr.strip_prefix("os.environ[") {
+        let name = unquote(rest.strip_suffix(']')?)?;
+        return env_map.get(name).cloned();
+    }
+
+    let call = parse_call(expr)?;
+    match call.function {
+        "str" | "int" => eval_python(call.args.first()?, env_map),
+        "os.environ.get" | "os.getenv" | "env" | "env.str" | "env.int" | "config" => {
+            let name = unquote(call.args.first()?)?;
+            env_map.get(name).cloned().or_else(|| {
+                call.args
+                    .get(1)
+                    .or_else(|| call.kwargs.get("default"))
+                    .and_then(|default| eval_python(default, env_map))
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `dj_database_url.config()`, `dj_database_url.parse(...)` and `env.db()`, which all read a URL.
+fn eval_database_url(expr: &str, env_map: &HashMap<String, String>) -> Option<String> {
+    let call = parse_call(expr)?;
+    match call.function {
+        "dj_database_url.config" => {
+            let var = call
+                .kwargs
+                .get("env")
+                .and_then(|e| unquote(e))
+                .unwrap_or("DATABASE_URL");
+            env_map
+                .get(var)
+                .cloned()
+                .or_else(|| eval_python(call.kwargs.get("default")?, env_map))
+        }
+        "dj_database_url.parse" => eval_python(call.args.first()?, env_map),
+        "env.db" | "env.db_url" => {
+            let var = call
+                .args
+                .first()
+                .and_then(|a| unquote(a))
+                .unwrap_or("DATABASE_URL");
+            env_map.get(var).cloned().or_else(|| {
+                call.args
+                    .get(1)
+                    .or_else(|| call.kwargs.get("default"))
+                    .and_then(|default| eval_python(default, env_map))
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The expression of the `"default"` database in the `DATABASES` setting.
+fn default_database(settings: &str) -> Option<&str> {
+    let start = settings.find("DATABASES")?;
+    let open = start + settings[start..].find('{')?;
+    let databases = bracketed(settings, open)?;
+
+    split_top_level(databases).into_iter().find_map(|entry| {
+        let (key, value) = entry.split_once(':')?;
+        (unquote(key)? == "default").then_some(value.trim())
+    })
+}
+
+fn get_pg_config_from_settings(
+    settings: &str,
+    env_map: &HashMap<String, String>,
+) -> anyhow::Result<PgConfig> {
+    let database = default_database(settings)
+        .ok_or_else(|| anyhow::anyhow!("No DATABASES[\"default\"] found in Django settings"))?;
+
+    if !database.starts_with('{') {
+        let url = eval_database_url(database, env_map)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported Django default database: {}", database))?;
+        return super::parse_conn_str(&url);
+    }
+
+    let fields: HashMap<&str, Option<String>> =
+        split_top_level(bracketed(database, 0).unwrap_or(""))
+            .into_iter()
+            .filter_map(|entry| {
+                let (key, value) = entry.split_once(':')?;
+                Some((unquote(key)?, eval_python(value, env_map)))
+            })
+            .collect();
+    let field = |key: &str| fields.get(key).cloned().flatten().filter(|v| !v.is_empty());
+
+    // postgresql, postgis or a psycopg based backend
+    if let Some(engine) = field("ENGINE") {
+        if !engine.contains("postg") && !engine.contains("psycopg") {
+            return Err(anyhow::anyhow!(
+                "Django default database uses {}, not Postgres",
+                engine
+            ));
+        }
+    }
+
+    let mut config = Config::new();
+    config
+        .host(field("HOST").as_deref().unwrap_or("localhost"))
+        .port(field("PORT").and_then(|p| p.parse().ok()).unwrap_or(5432))
+        .user(field("USER").as_deref().unwrap_or("postgres"))
+        .dbname(
+            &field("NAME")
+                .ok_or_else(|| anyhow::anyhow!("No NAME for the Django default database"))?,
+        );
+    if let Some(password) = field("PASSWORD") {
+        config.password(password);
+    }
+    PgConfig::from_config(config)
+}
+
+// e.g. os.environ.setdefault("DJANGO_SETTINGS_MODULE", "mysite.settings")
+fn settings_module(manage_py: &str) -> Option<String> {
+    let start = manage_py.find(SETTINGS_MODULE_ENV)?;
+    let rest = &manage_py[start + SETTINGS_MODULE_ENV.len()..];
+    let rest = rest.trim_start_matches(['"', '\'']).trim_start();
+    let rest = rest.strip_prefix(',')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let end = rest[1..].find(quote)?;
+    Some(rest[1..1 + end].to_string())
+}
+
+fn settings_path(project_dir: &Path, module: &str) -> Option<PathBuf> {
+    let base = project_dir.join(module.replace('.', "/"));
+    [base.with_extension("py"), base.join("__init__.py")]
+        .into_iter()
+        .find(|p| p.exists())
+}
+
+pub(crate) fn get_pg_config(skip_dotenv: bool) -> anyhow::Result<PgConfig> {
+    let cwd = std::env::current_dir()?;
+    let manage_py = cwd.join("manage.py");
+    if !manage_py.exists() {
+        return Err(anyhow::anyhow!("No Django manage.py found"));
+    }
+
+    let env_map = super::env::load_env(&cwd, skip_dotenv);
+    let module = env_map
+        .get(SETTINGS_MODULE_ENV)
+        .cloned()
+        .or_else(|| settings_module(&fs::read_to_string(&manage_py).ok()?))
+        .ok_or_else(|| anyhow::anyhow!("No {} found in manage.py", SETTINGS_MODULE_ENV))?;
+    let file_path = settings_path(&cwd, &module)
+        .ok_or_else(|| anyhow::anyhow!("No Django settings module {} found", module))?;
+
+    log::debug!(
+        "Checking for Django default database in {}",
+        file_path.display()
+    );
+    let config = get_pg_config_from_settings(&fs::read_to_string(&file_path)?, &env_map)?;
+    log::info!("Using Django default database from {}", file_path.display());
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::config::Host;
+
+    use super::*;
+
+    const SETTINGS_PY: &str = r#"
+import os
+
+DATABASES = {
+    "default": {
+        "ENGINE": "django.db.backends.postgresql",
+        "NAME": os.environ.get("POSTGRES_DB", "mysite"),
+        "USER": os.getenv("POSTGRES_USER", default="django"),
+        "PASSWORD": env("POSTGRES_PASSWORD"),
+        "HOST": "127.0.0.1",
+        "PORT": int(os.environ.get("POSTGRES_PORT", 5432)),
+        "OPTIONS": {"connect_timeout": 5},
+    },
+    "legacy": {
+        "ENGINE": "django.db.backends.sqlite3",
+    },
+}
+"#;
+
+    #[test]
+    fn test_settings_dict() {
+        let env_map = HashMap::from([
+            ("POSTGRES_PASSWORD".to_string(), "secret".to_string()),
+            ("POSTGRES_PORT".to_string(), "5433".to_string()),
+        ]);
+        let config = get_pg_config_from_settings(SETTINGS_PY, &env_map).unwrap();
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("127.0.0.1".to_string())]);
+        assert_eq!(config.get_ports(), vec![5433]);
+        assert_eq!(config.get_user(), Some("django"));
+        assert_eq!(config.get_password(), Some(&b"secret"[..]));
+        assert_eq!(config.get_dbname(), Some("mysite"));
+    }
+
+    #[test]
+    fn test_database_url_settings() {
+        let settings = r#"DATABASES = {'default': dj_database_url.config(default='postgres://app:pw@localhost:5432/app', conn_max_age=600)}"#;
+        let config = get_pg_config_from_settings(settings, &HashMap::new()).unwrap();
+        assert_eq!(config.get_dbname(), Some("app"));
+
+        let env_map = HashMap::from([(
+            "DATABASE_URL".to_string(),
+            "postgres://env@localhost/from_env".to_string(),
+        )]);
+        let settings = "DATABASES = {\"default\": env.db()}";
+        let config = get_pg_config_from_settings(settings, &env_map).unwrap();
+        assert_eq!(config.get_dbname(), Some("from_env"));
+    }
+
+    #[test]
+    fn test_settings_module() {
+        let manage_py = r#"os.environ.setdefault("DJANGO_SETTINGS_MODULE", "mysite.settings.dev")"#;
+        assert_eq!(
+            settings_module(manage_py),
+            Some("mysite.settings.dev".to_string())
+        );
+    }
+}
@@ -1,16 +1,405 @@
-use docker_compose_types::{Compose, Ports};
+use docker_compose_types::{Compose, ComposeSecret, EnvFile, Environment, Ports, Service, Volumes};
+use serde_yaml::{Mapping, Value};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use tokio_postgres::Config;
 
-fn parse_docker_compose(file_path: &Path) -> anyhow::Result<Compose> {
-    let mut file = File::open(file_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let compose_file: Compose = serde_yaml::from_str(&contents)?;
-    Ok(compose_file)
+use super::PgConfig;
+
+// As per: https://docs.docker.com/compose/compose-application-model/#the-compose-file
+const COMPOSE_FILES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+];
+
+// Images that run a Postgres server without "postgres" in their name, like postgis/postgis
+// Matched against the last segment of the repository, so `postgrest/postgrest` or `postgres-exporter` don't count
+const POSTGRES_IMAGES: [&str; 6] = [
+    "postgres",
+    "postgresql",
+    "postgis",
+    "timescaledb",
+    "timescaledb-ha",
+    "pgvector",
+];
+
+fn override_files(file_name: &str) -> Vec<String> {
+    let stem = file_name.trim_end_matches(".yaml").trim_end_matches(".yml");
+    vec![
+        format!("{}.override.yaml", stem),
+        format!("{}.override.yml", stem),
+    ]
+}
+
+// Same lookup as `docker compose`: COMPOSE_FILE, or the first compose file and its override
+fn find_compose_files(cwd: &Path) -> Vec<PathBuf> {
+    if let Ok(compose_file) = env::var("COMPOSE_FILE") {
+        if !compose_file.is_empty() {
+            let separator = env::var("COMPOSE_PATH_SEPARATOR")
+                .unwrap_or_else(|_| if cfg!(windows) { ";" } else { ":" }.to_string());
+            return compose_file
+                .split(separator.as_str())
+                .filter(|f| !f.is_empty())
+                .map(|f| cwd.join(f))
+                .collect();
+        }
+    }
+
+    let Some(file_name) = COMPOSE_FILES.iter().find(|f| cwd.join(f).exists()) else {
+        return vec![];
+    };
+    let mut files = vec![cwd.join(file_name)];
+    files.extend(
+        override_files(file_name)
+            .into_iter()
+            .map(|f| cwd.join(f))
+            .find(|f| f.exists()),
+    );
+    files
+}
+
+// The process env takes precedence over the project .env file
+fn load_project_env(project_dir: &Path) -> HashMap<String, String> {
+    let mut env_map = super::env::read_dotenv_file(&project_dir.join(".env")).unwrap_or_default();
+    env_map.extend(env::vars());
+    env_map
+}
+
+/// Substitutes `$VAR`, `${VAR}`, `${VAR:-default}`, `${VAR-default}`, `${VAR:+alt}`, `${VAR+alt}`,
+/// `${VAR:?error}` and `${VAR?error}` like Compose does, with `$$` escaping a `$`.
+fn interpolate(input: &str, env_map: &HashMap<String, String>) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            let end = matching_brace(braced)
+                .ok_or_else(|| anyhow::anyhow!("unterminated variable reference ${{{}", braced))?;
+            output.push_str(&expand(&braced[..end], env_map)?);
+            rest = &braced[end + 1..];
+        } else {
+            let name_len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if name_len == 0 {
+                output.push('$');
+            } else {
+                output.push_str(env_map.get(&rest[..name_len]).map_or("", |v| v));
+            }
+            rest = &rest[name_len..];
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Interpolates the string values of a parsed document, so comments and keys are left alone like Compose does.
+fn interpolate_yaml(value: &mut Value, env_map: &HashMap<String, String>) -> anyhow::Result<()> {
+    match value {
+        Value::String(s) => *s = interpolate(s, env_map)?,
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_yaml(item, env_map)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, item) in mapping.iter_mut() {
+                interpolate_yaml(item, env_map)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_yaml(&mut tagged.value, env_map)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+// Defaults can contain nested variable references
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn expand(expr: &str, env_map: &HashMap<String, String>) -> anyhow::Result<String> {
+    let name_len = expr
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(expr.len());
+    let (name, modifier) = expr.split_at(name_len);
+    let value = env_map.get(name);
+    let is_set = value.is_some();
+    let is_non_empty = value.is_some_and(|v| !v.is_empty());
+
+    let (operator, arg) = match modifier {
+        "" => return Ok(value.cloned().unwrap_or_default()),
+        m if m.starts_with(":-") || m.starts_with(":+") || m.starts_with(":?") => m.split_at(2),
+        m if m.starts_with('-') || m.starts_with('+') || m.starts_with('?') => m.split_at(1),
+        m => {
+            return Err(anyhow::anyhow!(
+                "invalid variable reference ${{{}{}}}",
+                name,
+                m
+            ))
+        }
+    };
+    let arg = || interpolate(arg, env_map);
+
+    match operator {
+        ":-" if !is_non_empty => arg(),
+        "-" if !is_set => arg(),
+        ":+" if is_non_empty => arg(),
+        "+" if is_set => arg(),
+        ":+" | "+" => Ok(String::new()),
+        ":?" if !is_non_empty => Err(anyhow::anyhow!(
+            "required variable {} is missing a value: {}",
+            name,
+            arg()?
+        )),
+        "?" if !is_set => Err(anyhow::anyhow!(
+            "required variable {} is missing a value: {}",
+            name,
+            arg()?
+        )),
+        _ => Ok(value.cloned().unwrap_or_default()),
+    }
+}
+
+// Lists become maps, so overrides are merged by variable name, the same way Compose does
+fn normalize_services(doc: &mut Value) {
+    let Some(services) = doc.get_mut("services").and_then(Value::as_mapping_mut) else {
+        return;
+    };
+    for (_, service) in services.iter_mut() {
+        let Some(service) = service.as_mapping_mut() else {
+            continue;
+        };
+        if let Some(Value::Sequence(list)) = service.get("environment") {
+            let map: Mapping = list
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|entry| match entry.split_once('=') {
+                    Some((k, v)) => (Value::from(k), Value::from(v)),
+                    None => (Value::from(entry), Value::Null),
+                })
+                .collect();
+            service.insert(Value::from("environment"), Value::Mapping(map));
+        }
+        // The long syntax, `- path: .env.local` with `required: false`, is not supported by docker-compose-types
+        if let Some(Value::Sequence(list)) = service.get_mut("env_file") {
+            for entry in list.iter_mut() {
+                if let Some(path) = entry.get("path").cloned() {
+                    *entry = path;
+                }
+            }
+        }
+    }
+}
+
+fn merge_yaml(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Mapping(base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+fn load_compose(files: &[PathBuf], env_map: &HashMap<String, String>) -> anyhow::Result<Compose> {
+    let mut merged = Value::Null;
+    for file_path in files {
+        log::debug!(
+            "Checking for Docker-compose Postgres config in {}",
+            file_path.display()
+        );
+        let mut doc: Value = serde_yaml::from_str(&fs::read_to_string(file_path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))?;
+        interpolate_yaml(&mut doc, env_map)
+            .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))?;
+        normalize_services(&mut doc);
+
+        if merged.is_null() {
+            merged = doc;
+        } else {
+            merge_yaml(&mut merged, doc);
+        }
+    }
+    Ok(serde_yaml::from_value(merged)?)
+}
+
+fn is_postgres_image(image: &str) -> bool {
+    // e.g. docker.io/library/postgres:16-alpine or ghcr.io/org/postgis/postgis:16-3.4@sha256:...
+    let without_digest = image.split('@').next().unwrap_or(image);
+    let name = match without_digest.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => without_digest,
+    };
+    let last_segment = name.rsplit('/').next().unwrap_or(name);
+
+    POSTGRES_IMAGES.contains(&last_segment)
+}
+
+// Services with profiles only start when one of them is enabled
+fn is_enabled(service: &Service, active_profiles: &[String]) -> bool {
+    service.profiles.is_empty() || service.profiles.iter().any(|p| active_profiles.contains(p))
+}
+
+fn postgres_services<'a>(
+    compose: &'a Compose,
+    active_profiles: &[String],
+) -> Vec<(&'a str, &'a Service)> {
+    compose
+        .services
+        .0
+        .iter()
+        .filter_map(|(name, service)| service.as_ref().map(|s| (name.as_str(), s)))
+        .filter(|(name, service)| {
+            let is_postgres = service.image.as_deref().is_some_and(is_postgres_image);
+            if is_postgres && !is_enabled(service, active_profiles) {
+                log::debug!(
+                    "Skipping Docker-compose service {} as none of its profiles are enabled: {}",
+                    name,
+                    service.profiles.join(", ")
+                );
+                return false;
+            }
+            is_postgres
+        })
+        .collect()
+}
+
+// `environment` takes precedence over `env_file`, variables without a value come from the project env
+fn service_env(
+    service: &Service,
+    project_dir: &Path,
+    project_env: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut env_map = HashMap::new();
+
+    let env_files = match &service.env_file {
+        Some(EnvFile::Simple(file)) => vec![file.clone()],
+        Some(EnvFile::List(files)) => files.clone(),
+        None => vec![],
+    };
+    for env_file in env_files {
+        let path = project_dir.join(&env_file);
+        match super::env::read_dotenv_file(&path) {
+            Ok(vars) => env_map.extend(vars),
+            Err(e) => log::warn!("Failed to read env_file {}: {}", path.display(), e),
+        }
+    }
+
+    match &service.environment {
+        Environment::List(env_list) => {
+            for entry in env_list {
+                match entry.split_once('=') {
+                    Some((k, v)) => env_map.insert(k.to_string(), v.to_string()),
+                    None => project_env
+                        .get(entry)
+                        .and_then(|v| env_map.insert(entry.to_string(), v.clone())),
+                };
+            }
+        }
+        Environment::KvPair(kv) => {
+            for (k, v) in kv {
+                match v {
+                    Some(v) => env_map.insert(k.to_string(), v.to_string()),
+                    None => project_env
+                        .get(k)
+                        .and_then(|v| env_map.insert(k.to_string(), v.clone())),
+                };
+            }
+        }
+    }
+
+    env_map
+}
+
+/// Maps a path inside the container, like `/run/secrets/db_password`, to the file on the host.
+fn host_path(
+    container_path: &str,
+    service: &Service,
+    compose: &Compose,
+    project_dir: &Path,
+) -> Option<PathBuf> {
+    if let Some(secret_name) = container_path.strip_prefix("/run/secrets/") {
+        let secret = compose
+            .secrets
+            .as_ref()
+            .and_then(|secrets| secrets.0.get(secret_name))
+            .and_then(|secret| secret.as_ref());
+        if let Some(ComposeSecret::File(file)) = secret {
+            return Some(project_dir.join(file));
+        }
+    }
+
+    service.volumes.iter().find_map(|volume| {
+        let (source, target) = match volume {
+            Volumes::Simple(spec) => {
+                let mut parts = spec.splitn(3, ':');
+                (parts.next()?.to_string(), parts.next()?.to_string())
+            }
+            Volumes::Advanced(advanced) => (advanced.source.clone()?, advanced.target.clone()),
+        };
+        // Only bind mounts, named volumes are not reachable from the host
+        if !(source.starts_with('.') || source.starts_with('/') || source.starts_with('~')) {
+            return None;
+        }
+        let relative = Path::new(container_path).strip_prefix(&target).ok()?;
+        Some(project_dir.join(source).join(relative))
+    })
+}
+
+/// The official image reads `POSTGRES_PASSWORD` and friends from a file when `<VAR>_FILE` is set.
+fn env_or_file(
+    var: &str,
+    env_map: &HashMap<String, String>,
+    service: &Service,
+    compose: &Compose,
+    project_dir: &Path,
+) -> Option<String> {
+    if let Some(value) = env_map.get(var) {
+        return Some(value.clone());
+    }
+    let container_path = env_map.get(&format!("{}_FILE", var))?;
+    let Some(path) = host_path(container_path, service, compose, project_dir) else {
+        log::warn!(
+            "Could not map {}_FILE={} to a file on the host",
+            var,
+            container_path
+        );
+        return None;
+    };
+    match fs::read_to_string(&path) {
+        Ok(value) => Some(value.trim_end_matches(['\r', '\n']).to_string()),
+        Err(e) => {
+            log::warn!("Failed to read {}_FILE {}: {}", var, path.display(), e);
+            None
+        }
+    }
 }
 
 fn parse_port(ports: &Ports, env_map: &HashMap<String, String>) -> u16 {
@@ -35,7 +424,7 @@ fn parse_port(ports: &Ports, env_map: &HashMap<String, String>) -> u16 {
             .iter()
             .find_map(|p| {
                 p.published.as_ref().map(|p| match p {
-                    docker_compose_types::PublishedPort::Single(sp) => Some(sp.clone()),
+                    docker_compose_types::PublishedPort::Single(sp) => Some(*sp),
                     // NOTE: this does not make sense for the official Postgres image
                     docker_compose_types::PublishedPort::Range(_) => None,
                 })
@@ -51,132 +440,242 @@ fn parse_port(ports: &Ports, env_map: &HashMap<String, String>) -> u16 {
     })
 }
 
-fn get_pg_config_from_docker_compose(compose: &Compose) -> Option<Config> {
-    for (service_name, opt_service) in &compose.services.0 {
-        if let Some(service) = opt_service {
-            match &service.image {
-                Some(image) => {
-                    if image.contains("postgres") {
-                        log::info!(
-                            "Found Postgres service in Docker-compose YAML file: {}",
-                            service_name
-                        );
-
-                        let env = &service.environment;
-
-                        let env_map = match env {
-                            docker_compose_types::Environment::List(env_list) => env_list
-                                .iter()
-                                .map(|env| env.split_once('='))
-                                .filter_map(|opt| opt.map(|(k, v)| (k.to_string(), v.to_string())))
-                                .collect::<HashMap<String, String>>(),
-                            docker_compose_types::Environment::KvPair(kv) => kv
-                                .iter()
-                                .filter_map(|(k, v)| {
-                                    if let Some(v) = v {
-                                        Some((k.to_string(), v.to_string()))
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect::<HashMap<String, String>>(),
-                        };
-
-                        let mut config = Config::new();
-
-                        config.host(
-                            env_map
-                                .get("POSTGRES_HOST")
-                                .unwrap_or(&"localhost".to_string()),
-                        );
-
-                        config.port(parse_port(&service.ports, &env_map));
-
-                        config.user(
-                            env_map
-                                .get("POSTGRES_USER")
-                                .unwrap_or(&"postgres".to_string()),
-                        );
-
-                        config.password(
-                            env_map
-                                .get("POSTGRES_PASSWORD")
-                                .unwrap_or(&"postgres".to_string()),
-                        );
-
-                        config.dbname(
-                            env_map
-                                .get("POSTGRES_DB")
-                                .unwrap_or(&"postgres".to_string()),
-                        );
-
-                        return Some(config);
-                    } else {
-                        continue;
-                    }
-                }
-                None => {
-                    continue;
-                }
-            }
+fn config_from_service(
+    service: &Service,
+    compose: &Compose,
+    project_dir: &Path,
+    project_env: &HashMap<String, String>,
+) -> anyhow::Result<PgConfig> {
+    let env_map = service_env(service, project_dir, project_env);
+    let var = |name: &str| env_or_file(name, &env_map, service, compose, project_dir);
+
+    let user = var("POSTGRES_USER").unwrap_or("postgres".to_string());
+    // The official image defaults the database to the user name
+    let dbname = var("POSTGRES_DB").unwrap_or_else(|| user.clone());
+
+    let mut config = Config::new();
+    config
+        .host(
+            env_map
+                .get("POSTGRES_HOST")
+                .unwrap_or(&"localhost".to_string()),
+        )
+        .port(parse_port(&service.ports, &env_map))
+        .user(&user)
+        .dbname(&dbname);
+    // Without one the image only starts with POSTGRES_HOST_AUTH_METHOD=trust, which needs none
+    match var("POSTGRES_PASSWORD") {
+        Some(password) => {
+            config.password(password);
         }
+        None => log::debug!("No POSTGRES_PASSWORD set for the Docker-compose service"),
     }
-    None
-}
 
-pub(crate) fn get_pg_config() -> anyhow::Result<Config> {
-    let cwd = std::env::current_dir().unwrap();
-    // As per: https://docs.docker.com/compose/compose-application-model/#the-compose-file
-    let yaml_file_preferences = vec![
-        "compose.yaml",
-        "compose.yml",
-        "docker-compose.yml",
-        "docker-compose.yaml",
-    ];
+    PgConfig::from_config(config)
+}
 
-    for yaml_file_preference in yaml_file_preferences {
-        let file_path = cwd.join(yaml_file_preference);
+fn choose_service<'a>(
+    services: &[(&'a str, &'a Service)],
+    wanted: Option<&str>,
+    interactive: bool,
+) -> anyhow::Result<(&'a str, &'a Service)> {
+    let names = || {
+        services
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
 
-        if !file_path.exists() {
-            continue;
+    match (wanted, services) {
+        (Some(wanted), _) => services
+            .iter()
+            .find(|(name, _)| *name == wanted)
+            .copied()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No enabled Postgres service called {} in Docker-compose, found: {}",
+                    wanted,
+                    names()
+                )
+            }),
+        (None, []) => Err(anyhow::anyhow!(
+            "No Postgres service found in Docker-compose"
+        )),
+        (None, [service]) => Ok(*service),
+        (None, _) if interactive => {
+            let selection =
+                dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                    .with_prompt(
+                        "Several Postgres services found in Docker-compose, which one to use?",
+                    )
+                    .items(&services.iter().map(|(name, _)| *name).collect::<Vec<_>>())
+                    .default(0)
+                    .interact()?;
+            Ok(services[selection])
         }
+        (None, _) => Err(anyhow::anyhow!(
+            "Several Postgres services found in Docker-compose: {}, pick one with --service",
+            names()
+        )),
+    }
+}
 
-        log::debug!(
-            "Checking for Docker-compose Postgres config in {}",
-            file_path.display()
-        );
+pub(crate) fn get_pg_config(service: Option<&str>, interactive: bool) -> anyhow::Result<PgConfig> {
+    let cwd = env::current_dir()?;
+    let files = find_compose_files(&cwd);
+    let Some(project_dir) = files.first().and_then(|f| f.parent()) else {
+        return Err(anyhow::anyhow!("No Docker-compose file found"));
+    };
 
-        let docker_compose = match parse_docker_compose(&file_path) {
-            Ok(docker_compose) => docker_compose,
-            Err(e) => {
-                log::warn!(
-                    "Failed to parse Docker-compose YAML file {}: {}",
-                    file_path.display(),
-                    e
-                );
-                continue;
-            }
-        };
+    let project_env = load_project_env(project_dir);
+    let compose = load_compose(&files, &project_env)?;
 
-        match get_pg_config_from_docker_compose(&docker_compose) {
-            Some(config) => {
-                log::info!(
-                    "Using Docker-compose Postgres config from {}",
-                    file_path.display()
-                );
-                return Ok(config);
-            }
-            None => {
-                log::debug!(
-                    "No Docker-compose Postgres config found in {}",
-                    file_path.display()
-                );
-                continue;
-            }
-        };
+    let active_profiles: Vec<String> = project_env
+        .get("COMPOSE_PROFILES")
+        .map(|p| p.split(',').map(|p| p.trim().to_string()).collect())
+        .unwrap_or_default();
+
+    let services = postgres_services(&compose, &active_profiles);
+    let (service_name, service) = choose_service(&services, service, interactive)?;
+
+    log::info!(
+        "Using Docker-compose Postgres service {} from {}",
+        service_name,
+        files
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    config_from_service(service, &compose, project_dir, &project_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::config::Host;
+
+    use super::*;
+
+    fn env_map(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let env = env_map(&[("PORT", "5433"), ("EMPTY", ""), ("USER", "app")]);
+        assert_eq!(
+            interpolate(
+                "${PORT:-5432}:5432 $USER ${MISSING:-${USER}_db} $$HOME",
+                &env
+            )
+            .unwrap(),
+            "5433:5432 app app_db $HOME"
+        );
+        assert_eq!(interpolate("${EMPTY:-x} ${EMPTY-x}", &env).unwrap(), "x ");
+        assert_eq!(
+            interpolate("${USER:+set} ${MISSING+set}", &env).unwrap(),
+            "set "
+        );
+        assert!(interpolate("${MISSING:?is required}", &env).is_err());
+        assert!(interpolate("${PORT", &env).is_err());
+    }
+
+    #[test]
+    fn test_is_postgres_image() {
+        for image in [
+            "postgres",
+            "postgres:16-alpine",
+            "docker.io/library/postgres:16@sha256:abc",
+            "postgis/postgis:16-3.4",
+            "timescale/timescaledb:latest-pg16",
+            "timescale/timescaledb-ha:pg16",
+            "supabase/postgres:15.1.0.147",
+            "pgvector/pgvector:pg16",
+            "localhost:5000/postgis/postgis",
+            "bitnami/postgresql:16",
+        ] {
+            assert!(is_postgres_image(image), "{}", image);
+        }
+        for image in [
+            "redis:7",
+            "hasura/graphql-engine",
+            "postgrest/postgrest:v12.0.2",
+            "prometheuscommunity/postgres-exporter",
+            "quay.io/prometheuscommunity/postgres-exporter:v0.15.0",
+            "eeshugerman/postgres-backup-s3:16",
+        ] {
+            assert!(!is_postgres_image(image), "{}", image);
+        }
     }
 
-    return Err(anyhow::Error::msg(
-        "No valid Docker-compose Postgres config found",
-    ));
+    const COMPOSE_YAML: &str = r#"
+services:
+  db:
+    image: postgis/postgis:16-3.4
+    # In CI: ${CI_DB_PORT:?is required}
+    ports:
+      - "${DB_PORT:-5432}:5432"
+    environment:
+      - POSTGRES_USER=app
+      - POSTGRES_PASSWORD_FILE=/run/secrets/db_password
+  test-db:
+    image: postgres:16
+    profiles: ["test"]
+    environment:
+      POSTGRES_PASSWORD: test
+  cache:
+    image: redis:7
+  api:
+    image: postgrest/postgrest:v12.0.2
+    environment:
+      PGRST_DB_URI: postgres://app@db:5432/app_dev
+secrets:
+  db_password:
+    file: ./secrets/db_password.txt
+"#;
+
+    const OVERRIDE_YAML: &str = r#"
+services:
+  db:
+    environment:
+      POSTGRES_DB: app_dev
+"#;
+
+    #[test]
+    fn test_compose_with_override_profiles_and_secrets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("secrets")).unwrap();
+        std::fs::write(dir.join("compose.yaml"), COMPOSE_YAML).unwrap();
+        std::fs::write(dir.join("compose.override.yaml"), OVERRIDE_YAML).unwrap();
+        std::fs::write(dir.join("secrets/db_password.txt"), "s3cret\n").unwrap();
+
+        let files = vec![dir.join("compose.yaml"), dir.join("compose.override.yaml")];
+        let project_env = env_map(&[("DB_PORT", "5544")]);
+        let compose = load_compose(&files, &project_env).unwrap();
+
+        let services = postgres_services(&compose, &[]);
+        assert_eq!(
+            services.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec!["db"]
+        );
+        let both = postgres_services(&compose, &["test".to_string()]);
+        assert_eq!(both.len(), 2);
+        assert!(choose_service(&both, None, false).is_err());
+        assert_eq!(
+            choose_service(&both, Some("test-db"), false).unwrap().0,
+            "test-db"
+        );
+
+        let config = config_from_service(services[0].1, &compose, dir, &project_env).unwrap();
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("localhost".to_string())]);
+        assert_eq!(config.get_ports(), vec![5544]);
+        assert_eq!(config.get_user(), Some("app"));
+        assert_eq!(config.get_password(), Some(&b"s3cret"[..]));
+        assert_eq!(config.get_dbname(), Some("app_dev"));
+    }
 }
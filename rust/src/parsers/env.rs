@@ -1,46 +1,233 @@
-use std::env;
+use std::{collections::HashMap, env, net::IpAddr, path::Path, time::Duration};
 
+use tokio_postgres::config::{ChannelBinding, TargetSessionAttrs};
 use tokio_postgres::Config;
 
-pub(crate) fn config_from_env() -> Result<Config, anyhow::Error> {
-    // Prefer DATABASE_URL over PG* env vars
-
-    let database_config = match env::var("DATABASE_URL") {
-        Ok(url) => {
-            if url.is_empty() {
-                log::debug!("DATABASE_URL is empty");
-                Err(())
-            } else {
-                log::debug!("Parsing DATABASE_URL");
-                Ok(url.parse::<Config>()?)
+use super::{ParamOrigin, PgConfig, Source};
+
+/// Reads the variables of a .env file without touching the process environment.
+// NOTE: deprecated in favour of `from_path` + `var`, which would mutate the process env
+#[allow(deprecated)]
+pub(crate) fn read_dotenv_file(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    Ok(dotenv::from_path_iter(path)?.collect::<Result<HashMap<_, _>, _>>()?)
+}
+
+// Highest precedence first, like dotenv-flow and most frameworks
+const DOTENV_FILES: [&str; 4] = [
+    ".env.development.local",
+    ".env.local",
+    ".env.development",
+    ".env",
+];
+
+/// The process env on top of the `.env*` files of `dir`, unless they are opted out of.
+pub(crate) fn load_env(dir: &Path, skip_dotenv: bool) -> HashMap<String, String> {
+    let mut env_map = HashMap::new();
+    if !skip_dotenv {
+        for file_name in DOTENV_FILES.iter().rev() {
+            let path = dir.join(file_name);
+            if let Ok(vars) = read_dotenv_file(&path) {
+                log::debug!("Reading env vars from {}", path.display());
+                env_map.extend(vars);
             }
         }
-        Err(_) => Err(()),
-    };
+    }
+    env_map.extend(env::vars());
+    env_map
+}
+
+// The libpq env vars and the connection parameters they default, as per:
+// https://www.postgresql.org/docs/current/libpq-envars.html
+const PG_ENV_PARAMS: [(&str, &str); 11] = [
+    ("PGHOST", "host"),
+    ("PGHOSTADDR", "hostaddr"),
+    ("PGPORT", "port"),
+    ("PGDATABASE", "dbname"),
+    ("PGUSER", "user"),
+    ("PGPASSWORD", "password"),
+    ("PGOPTIONS", "options"),
+    ("PGAPPNAME", "application_name"),
+    ("PGCONNECT_TIMEOUT", "connect_timeout"),
+    ("PGTARGETSESSIONATTRS", "target_session_attrs"),
+    ("PGCHANNELBINDING", "channel_binding"),
+];
 
-    if database_config.is_ok() {
-        return Ok(database_config.unwrap());
+// Only these pick a server, the others just default whichever source is used, see `apply_env_defaults`
+const SERVER_PARAMS: [&str; 2] = ["host", "hostaddr"];
+
+fn pg_env_params(env_map: &HashMap<String, String>) -> Vec<(&'static str, String)> {
+    PG_ENV_PARAMS
+        .iter()
+        .filter_map(|(var, param)| {
+            env_map
+                .get(*var)
+                .filter(|value| !value.is_empty())
+                .map(|value| (*param, value.clone()))
+        })
+        .collect()
+}
+
+fn config_from_env_map(env_map: &HashMap<String, String>) -> Result<PgConfig, anyhow::Error> {
+    let var = |name: &str| env_map.get(name).cloned();
+
+    // Prefer DATABASE_URL over PGSERVICE over PG* env vars
+
+    if let Some(url) = var("DATABASE_URL") {
+        if url.is_empty() {
+            log::debug!("DATABASE_URL is empty");
+        } else {
+            log::debug!("Parsing DATABASE_URL");
+            return super::parse_conn_str(&url);
+        }
     }
 
-    let host = env::var("PGHOST").ok();
-    let port = env::var("PGPORT").ok().and_then(|p| p.parse().ok());
-    let user = env::var("PGUSER").ok();
-    let password = env::var("PGPASSWORD").ok();
-    let dbname = env::var("PGDATABASE").ok();
+    if let Some(service) = var(super::pg_service::PGSERVICE_ENV) {
+        if !service.is_empty() {
+            log::debug!("Using PGSERVICE {}", service);
+            return super::parse_conn_str(&format!(
+                "service={}",
+                crate::tls::quote_value(&service)
+            ));
+        }
+    }
 
-    if host.is_some() && port.is_some() && user.is_some() && password.is_some() && dbname.is_some()
+    let params = pg_env_params(env_map);
+    if !params
+        .iter()
+        .any(|(param, _)| SERVER_PARAMS.contains(param))
     {
-        let mut config = Config::new();
-        config
-            .host(host.as_deref().unwrap())
-            .port(port.unwrap())
-            .user(user.as_deref().unwrap())
-            .password(password.as_deref().unwrap())
-            .dbname(dbname.as_deref().unwrap());
-        Ok(config)
-    } else {
-        Err(anyhow::anyhow!("Missing required environment variables"))
+        return Err(anyhow::anyhow!(
+            "Neither DATABASE_URL, PGSERVICE, PGHOST nor PGHOSTADDR is set"
+        ));
+    }
+    log::debug!(
+        "Using {} env vars",
+        PG_ENV_PARAMS
+            .iter()
+            .filter(|(_, param)| params.iter().any(|(p, _)| p == param))
+            .map(|(var, _)| *var)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let conn_str = params
+        .iter()
+        .map(|(param, value)| format!("{}={}", param, crate::tls::quote_value(value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut config = super::parse_conn_str(&conn_str)?;
+    // Like libpq, the user defaults to the OS user, the database to the user
+    if config.get_user().is_none() {
+        config.user(var("USER").as_deref().unwrap_or("postgres"));
+        config.set_origin("user", ParamOrigin::Guessed(Source::Env));
+    }
+    Ok(config)
+}
+
+pub(crate) fn config_from_env(skip_dotenv: bool) -> Result<PgConfig, anyhow::Error> {
+    config_from_env_map(&load_env(&env::current_dir()?, skip_dotenv))
+}
+
+/// Fills the connection parameters a source left unset from the PG* env vars, like libpq does.
+/// Returns the parameters that were filled along with their env var.
+pub(crate) fn apply_env_defaults(
+    config: &mut Config,
+    skip_dotenv: bool,
+) -> Vec<(&'static str, &'static str)> {
+    let env_map = match env::current_dir() {
+        Ok(cwd) => load_env(&cwd, skip_dotenv),
+        Err(_) => env::vars().collect(),
+    };
+    apply_env_map_defaults(config, &env_map)
+}
+
+fn apply_env_map_defaults(
+    config: &mut Config,
+    env_map: &HashMap<String, String>,
+) -> Vec<(&'static str, &'static str)> {
+    let mut applied: Vec<(&'static str, &'static str)> = vec![];
+    for (var, param) in PG_ENV_PARAMS {
+        let Some(value) = env_map.get(var).filter(|value| !value.is_empty()).cloned() else {
+            continue;
+        };
+        match param {
+            "host" if config.get_hosts().is_empty() && config.get_hostaddrs().is_empty() => {
+                for host in value.split(',') {
+                    config.host(host);
+                }
+            }
+            // Goes with PGHOST when both are set, but never with a host of another source
+            "hostaddr"
+                if config.get_hostaddrs().is_empty()
+                    && (config.get_hosts().is_empty()
+                        || applied.iter().any(|(p, _)| *p == "host")) =>
+            {
+                let Ok(addrs) = value
+                    .split(',')
+                    .map(|addr| addr.trim().parse::<IpAddr>())
+                    .collect::<Result<Vec<_>, _>>()
+                else {
+                    log::warn!("Ignoring invalid {}={}", var, value);
+                    continue;
+                };
+                for addr in addrs {
+                    config.hostaddr(addr);
+                }
+            }
+            "port" if config.get_ports().is_empty() => {
+                for port in value.split(',').filter_map(|p| p.trim().parse().ok()) {
+                    config.port(port);
+                }
+            }
+            "dbname" if config.get_dbname().is_none() => {
+                config.dbname(&value);
+            }
+            "user" if config.get_user().is_none() => {
+                config.user(&value);
+            }
+            "password" if config.get_password().is_none() => {
+                config.password(value);
+            }
+            "options" if config.get_options().is_none() => {
+                config.options(&value);
+            }
+            "application_name" if config.get_application_name().is_none() => {
+                config.application_name(&value);
+            }
+            "connect_timeout" if config.get_connect_timeout().is_none() => {
+                if let Ok(seconds) = value.trim().parse() {
+                    config.connect_timeout(Duration::from_secs(seconds));
+                }
+            }
+            // Unset and the default can't be told apart, so only the default gets replaced
+            "target_session_attrs"
+                if config.get_target_session_attrs() == TargetSessionAttrs::Any =>
+            {
+                config.target_session_attrs(match value.as_str() {
+                    "any" => TargetSessionAttrs::Any,
+                    "read-write" => TargetSessionAttrs::ReadWrite,
+                    _ => {
+                        log::warn!("Ignoring unsupported {}={}", var, value);
+                        continue;
+                    }
+                });
+            }
+            "channel_binding" if config.get_channel_binding() == ChannelBinding::Prefer => {
+                config.channel_binding(match value.as_str() {
+                    "disable" => ChannelBinding::Disable,
+                    "prefer" => ChannelBinding::Prefer,
+                    "require" => ChannelBinding::Require,
+                    _ => {
+                        log::warn!("Ignoring unsupported {}={}", var, value);
+                        continue;
+                    }
+                });
+            }
+            _ => continue,
+        }
+        applied.push((param, var));
     }
+    applied
 }
 
 #[cfg(test)]
@@ -62,7 +249,7 @@ mod tests {
         env::set_var("PGPASSWORD", "password");
         env::set_var("PGDATABASE", "database");
 
-        let config = config_from_env().unwrap();
+        let config = config_from_env(true).unwrap();
         assert_eq!(config.get_hosts(), vec![Host::Tcp("localhost".to_string())]);
         assert_eq!(config.get_ports(), vec![5432]);
         assert_eq!(config.get_user(), Some("user"));
@@ -75,4 +262,115 @@ mod tests {
         env::remove_var("PGPASSWORD");
         env::remove_var("PGDATABASE");
     }
+
+    #[test]
+    fn test_partial_env_vars() {
+        let env_map = HashMap::from([
+            ("PGHOST".to_string(), "db.local".to_string()),
+            ("PGUSER".to_string(), "app".to_string()),
+            ("PGAPPNAME".to_string(), "schemamap".to_string()),
+            ("PGCONNECT_TIMEOUT".to_string(), "3".to_string()),
+        ]);
+        let config = config_from_env_map(&env_map).unwrap();
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("db.local".to_string())]);
+        assert_eq!(config.get_user(), Some("app"));
+        assert_eq!(config.get_password(), None);
+        assert_eq!(config.get_application_name(), Some("schemamap"));
+        assert_eq!(config.get_connect_timeout(), Some(&Duration::from_secs(3)));
+
+        let tuning_only = HashMap::from([("PGAPPNAME".to_string(), "schemamap".to_string())]);
+        assert!(config_from_env_map(&tuning_only).is_err());
+
+        // Without a host, they only fill in the gaps of another source
+        let port_only = HashMap::from([
+            ("PGPORT".to_string(), "5433".to_string()),
+            ("PGUSER".to_string(), "me".to_string()),
+        ]);
+        assert!(config_from_env_map(&port_only).is_err());
+
+        let host_only = HashMap::from([
+            ("PGHOST".to_string(), "db.local".to_string()),
+            ("USER".to_string(), "me".to_string()),
+        ]);
+        let config = config_from_env_map(&host_only).unwrap();
+        assert_eq!(config.get_user(), Some("me"));
+        assert_eq!(
+            config.origin("user"),
+            Some(ParamOrigin::Guessed(Source::Env))
+        );
+        assert_eq!(config.origin("host"), None);
+    }
+
+    #[test]
+    fn test_apply_env_defaults() {
+        let env_map = HashMap::from([
+            ("PGHOST".to_string(), "db.local".to_string()),
+            ("PGHOSTADDR".to_string(), "10.0.0.1".to_string()),
+            ("PGPORT".to_string(), "5433".to_string()),
+            ("PGUSER".to_string(), "me".to_string()),
+            ("PGTARGETSESSIONATTRS".to_string(), "read-write".to_string()),
+            ("PGCHANNELBINDING".to_string(), "require".to_string()),
+        ]);
+
+        let mut config: Config = "host=localhost user=app".parse().unwrap();
+        let applied = apply_env_map_defaults(&mut config, &env_map);
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("localhost".to_string())]);
+        assert!(config.get_hostaddrs().is_empty());
+        assert_eq!(config.get_ports(), vec![5433]);
+        assert_eq!(config.get_user(), Some("app"));
+        assert_eq!(
+            config.get_target_session_attrs(),
+            TargetSessionAttrs::ReadWrite
+        );
+        assert_eq!(config.get_channel_binding(), ChannelBinding::Require);
+        assert_eq!(
+            applied,
+            vec![
+                ("port", "PGPORT"),
+                ("target_session_attrs", "PGTARGETSESSIONATTRS"),
+                ("channel_binding", "PGCHANNELBINDING"),
+            ]
+        );
+
+        let mut config = Config::new();
+        apply_env_map_defaults(&mut config, &env_map);
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("db.local".to_string())]);
+        assert_eq!(
+            config.get_hostaddrs(),
+            ["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+
+        let mut config: Config = "channel_binding=disable".parse().unwrap();
+        apply_env_map_defaults(&mut config, &env_map);
+        assert_eq!(config.get_channel_binding(), ChannelBinding::Disable);
+
+        let unsupported = HashMap::from([(
+            "PGTARGETSESSIONATTRS".to_string(),
+            "prefer-standby".to_string(),
+        )]);
+        assert!(apply_env_map_defaults(&mut Config::new(), &unsupported).is_empty());
+    }
+
+    #[test]
+    fn test_load_env_precedence() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(
+            dir.join(".env"),
+            "SCHEMAMAP_TEST_A=env\nSCHEMAMAP_TEST_B=env\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join(".env.local"), "SCHEMAMAP_TEST_A=local\n").unwrap();
+
+        let env_map = load_env(dir, false);
+        assert_eq!(
+            env_map.get("SCHEMAMAP_TEST_A").map(String::as_str),
+            Some("local")
+        );
+        assert_eq!(
+            env_map.get("SCHEMAMAP_TEST_B").map(String::as_str),
+            Some("env")
+        );
+        assert_eq!(load_env(dir, true).get("SCHEMAMAP_TEST_A"), None);
+    }
 }
@@ -0,0 +1,233 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::tls::{is_url, parse_key_value_pairs, quote_value};
+
+// As per: https://www.postgresql.org/docs/current/libpq-pgservice.html
+pub(crate) const PGSERVICE_ENV: &str = "PGSERVICE";
+const PGSERVICEFILE_ENV: &str = "PGSERVICEFILE";
+const PGSYSCONFDIR_ENV: &str = "PGSYSCONFDIR";
+
+type Params = Vec<(String, String)>;
+
+/// The `key=value` lines of the `[service]` section, if the file has one.
+fn find_service(contents: &str, service: &str) -> anyhow::Result<Option<Params>> {
+    let mut in_section = false;
+    let mut params = None::<Params>;
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            // libpq uses the first matching section
+            if params.is_some() {
+                break;
+            }
+            in_section = name == service;
+            if in_section {
+                params = Some(vec![]);
+            }
+        } else if in_section {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("syntax error in service file, line {}", i + 1))?;
+            let key = key.trim();
+            if key == "service" {
+                return Err(anyhow::anyhow!(
+                    "nested service specifications not supported in service file, line {}",
+                    i + 1
+                ));
+            }
+            if let Some(params) = params.as_mut() {
+                params.push((key.to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    Ok(params)
+}
+
+// The per-user file is searched before the system-wide one
+fn service_files() -> Vec<PathBuf> {
+    let mut files = vec![];
+    match env::var(PGSERVICEFILE_ENV) {
+        Ok(path) if !path.is_empty() => files.push(PathBuf::from(path)),
+        _ => files.extend(dirs::home_dir().map(|home| home.join(".pg_service.conf"))),
+    }
+    if let Ok(dir) = env::var(PGSYSCONFDIR_ENV) {
+        files.push(Path::new(&dir).join("pg_service.conf"));
+    }
+    files
+}
+
+fn lookup_service(service: &str) -> anyhow::Result<Params> {
+    for file_path in service_files() {
+        let Ok(contents) = fs::read_to_string(&file_path) else {
+            continue;
+        };
+
+        let found = find_service(&contents, service)
+            .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))?;
+        if let Some(params) = found {
+            log::debug!("Using service \"{}\" from {}", service, file_path.display());
+            return Ok(params);
+        }
+    }
+    Err(anyhow::anyhow!(
+        "definition of service \"{}\" not found",
+        service
+    ))
+}
+
+fn url_params(conn_str: &str) -> anyhow::Result<Params> {
+    let url = url::Url::parse(conn_str).map_err(|e| {
+        anyhow::anyhow!(
+            "invalid connection URL: {}, use the key=value form along with service=",
+            e
+        )
+    })?;
+    let decode = |s: &str| -> anyhow::Result<String> {
+        Ok(percent_encoding::percent_decode_str(s)
+            .decode_utf8()?
+            .into_owned())
+    };
+
+    let mut params = vec![];
+    if !url.username().is_empty() {
+        params.push(("user".to_string(), decode(url.username())?));
+    }
+    if let Some(password) = url.password() {
+        params.push(("password".to_string(), decode(password)?));
+    }
+    if let Some(host) = url.host_str().filter(|h| !h.is_empty()) {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        params.push(("host".to_string(), decode(host)?));
+    }
+    if let Some(port) = url.port() {
+        params.push(("port".to_string(), port.to_string()));
+    }
+    let dbname = url.path().trim_start_matches('/');
+    if !dbname.is_empty() {
+        params.push(("dbname".to_string(), decode(dbname)?));
+    }
+    for (key, value) in url.query_pairs() {
+        params.push((key.into_owned(), value.into_owned()));
+    }
+    Ok(params)
+}
+
+/// Params given next to `service=` take precedence over the ones in the service file.
+fn merge(service_params: Params, explicit: Params) -> Params {
+    let mut merged: Params = service_params
+        .into_iter()
+        .filter(|(key, _)| !explicit.iter().any(|(k, _)| k == key))
+        .collect();
+    merged.extend(explicit);
+    merged
+}
+
+/// Expands a `service=` in a URL or key=value connection string into a key=value one.
+///
+/// Connection strings without a service are returned as-is.
+pub(crate) fn expand_service(conn_str: &str) -> anyhow::Result<String> {
+    // Cheap check, so plain connection strings are left to tokio_postgres for parsing
+    if !conn_str.contains("service") {
+        return Ok(conn_str.to_string());
+    }
+
+    let params = if is_url(conn_str) {
+        url_params(conn_str)?
+    } else {
+        parse_key_value_pairs(conn_str)?
+    };
+    let Some(service) = params
+        .iter()
+        .find(|(key, _)| key == "service")
+        .map(|(_, value)| value.clone())
+    else {
+        return Ok(conn_str.to_string());
+    };
+
+    let explicit = params
+        .into_iter()
+        .filter(|(key, _)| key != "service")
+        .collect();
+    let merged = merge(lookup_service(&service)?, explicit);
+
+    Ok(merged
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, quote_value(value)))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE_FILE: &str = r#"
+# Local development
+[localdev]
+host=localhost
+port=5433
+dbname=app_dev
+user=app
+sslmode=disable
+
+[staging]
+host = staging.example.com
+dbname = app
+"#;
+
+    #[test]
+    fn test_find_service() {
+        let params = find_service(SERVICE_FILE, "staging").unwrap().unwrap();
+        assert_eq!(
+            params,
+            vec![
+                ("host".to_string(), "staging.example.com".to_string()),
+                ("dbname".to_string(), "app".to_string()),
+            ]
+        );
+        assert_eq!(find_service(SERVICE_FILE, "prod").unwrap(), None);
+        assert!(find_service("[a]\nservice=b\n", "a").is_err());
+        assert!(find_service("[a]\nhost\n", "a").is_err());
+    }
+
+    #[test]
+    fn test_explicit_params_win() {
+        let service_params = find_service(SERVICE_FILE, "localdev").unwrap().unwrap();
+        let explicit = parse_key_value_pairs("dbname=other password='it\\'s'").unwrap();
+        let merged = merge(service_params, explicit);
+        assert!(merged.contains(&("dbname".to_string(), "other".to_string())));
+        assert!(!merged.contains(&("dbname".to_string(), "app_dev".to_string())));
+        assert!(merged.contains(&("port".to_string(), "5433".to_string())));
+        assert!(merged.contains(&("password".to_string(), "it's".to_string())));
+    }
+
+    #[test]
+    fn test_url_params() {
+        let params =
+            url_params("postgresql://us%40er:p%2Bss@[::1]:6432/my%20db?service=localdev").unwrap();
+        assert_eq!(
+            params,
+            vec![
+                ("user".to_string(), "us@er".to_string()),
+                ("password".to_string(), "p+ss".to_string()),
+                ("host".to_string(), "::1".to_string()),
+                ("port".to_string(), "6432".to_string()),
+                ("dbname".to_string(), "my db".to_string()),
+                ("service".to_string(), "localdev".to_string()),
+            ]
+        );
+        assert_eq!(
+            url_params("postgresql:///?service=localdev").unwrap(),
+            vec![("service".to_string(), "localdev".to_string())]
+        );
+    }
+}
@@ -1,57 +1,412 @@
 use std::{
-    fs::File,
-    io::{self, BufRead},
+    env,
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
 };
 
-use tokio_postgres::Config;
-
-fn parse_pgpass_line(line: &str) -> Option<Config> {
-    let parts: Vec<&str> = line.split(':').collect();
-    if parts.len() == 5 {
-        let mut config = Config::new();
-        config
-            .host(parts[0])
-            .port(parts[1].parse().ok()?)
-            .dbname(parts[2])
-            .user(parts[3])
-            .password(parts[4]);
-        Some(config)
-    } else {
-        None
+use tokio_postgres::{config::Host, Config};
+
+const PGPASSFILE_ENV: &str = "PGPASSFILE";
+const DEFAULT_PORT: u16 = 5432;
+
+// As per: https://www.postgresql.org/docs/current/libpq-pgpass.html
+#[derive(Debug, PartialEq, Eq)]
+struct PgpassEntry {
+    host: String,
+    port: String,
+    dbname: String,
+    user: String,
+    password: String,
+}
+
+/// The connection params an entry is matched against, `None` only matches a `*` wildcard.
+#[derive(Debug)]
+struct Target<'a> {
+    host: Option<&'a str>,
+    port: u16,
+    dbname: Option<&'a str>,
+    user: Option<&'a str>,
+}
+
+fn field_matches(pattern: &str, value: Option<&str>) -> bool {
+    pattern == "*" || Some(pattern) == value
+}
+
+impl PgpassEntry {
+    fn matches(&self, target: &Target) -> bool {
+        // libpq matches `localhost` against connections over the default unix socket too
+        let host_matches = field_matches(&self.host, target.host)
+            || (self.host == "localhost" && target.host.is_some_and(|h| h.starts_with('/')));
+
+        host_matches
+            && field_matches(&self.port, Some(&target.port.to_string()))
+            && field_matches(&self.dbname, target.dbname)
+            && field_matches(&self.user, target.user)
     }
 }
 
-pub(crate) fn get_pg_config() -> anyhow::Result<Config> {
-    if let Some(home_dir) = dirs::home_dir() {
-        let pgpass_path = home_dir.join(".pgpass");
-        if pgpass_path.exists() {
-            let file = File::open(pgpass_path)?;
-            for line in io::BufReader::new(file).lines() {
-                let line = line?;
-                if let Some(config) = parse_pgpass_line(&line) {
-                    return Ok(config);
+/// Splits a line on unescaped `:`, unescaping `\:` and `\\`, like libpq does.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    fields.last_mut().unwrap().push(next);
                 }
             }
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
         }
     }
-    Err(anyhow::anyhow!("No .pgpass file found"))
+    fields
 }
 
-#[cfg(test)]
-mod tests {
+fn parse_pgpass_line(line: &str) -> Option<PgpassEntry> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    // Anything after an unescaped `:` following the password is ignored by libpq
+    let mut fields = split_fields(line).into_iter();
+    Some(PgpassEntry {
+        host: fields.next()?,
+        port: fields.next()?,
+        dbname: fields.next()?,
+        user: fields.next()?,
+        password: fields.next()?,
+    })
+}
+
+fn pgpass_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var(PGPASSFILE_ENV) {
+        if !path.is_empty() {
+            return Some(PathBuf::from(path));
+        }
+    }
+    if cfg!(windows) {
+        dirs::config_dir().map(|dir| dir.join("postgresql").join("pgpass.conf"))
+    } else {
+        dirs::home_dir().map(|home| home.join(".pgpass"))
+    }
+}
 
-    use tokio_postgres::config::Host;
+// libpq ignores password files readable by others
+#[cfg(unix)]
+fn has_safe_permissions(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().mode() & 0o077 != 0 => {
+            log::warn!(
+                "Password file {} has group or world access, permissions should be u=rw (0600) or less, ignoring it",
+                path.display()
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+#[cfg(not(unix))]
+fn has_safe_permissions(_path: &Path) -> bool {
+    true
+}
+
+fn read_entries(path: &Path) -> anyhow::Result<Vec<PgpassEntry>> {
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("No password file {} found", path.display()));
+    }
+    if !has_safe_permissions(path) {
+        return Err(anyhow::anyhow!(
+            "Password file {} has unsafe permissions",
+            path.display()
+        ));
+    }
+
+    let file = File::open(path)?;
+    let mut entries = vec![];
+    for line in io::BufReader::new(file).lines() {
+        if let Some(entry) = parse_pgpass_line(&line?) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn find_password<'a>(entries: &'a [PgpassEntry], target: &Target) -> Option<&'a str> {
+    entries
+        .iter()
+        .find(|entry| entry.matches(target))
+        .map(|entry| entry.password.as_str())
+}
+
+fn target_of(config: &Config) -> Target<'_> {
+    let host = config.get_hosts().first().and_then(|host| match host {
+        Host::Tcp(host) => Some(host.as_str()),
+        #[cfg(unix)]
+        Host::Unix(path) => path.to_str(),
+    });
+    Target {
+        host,
+        port: config.get_ports().first().copied().unwrap_or(DEFAULT_PORT),
+        // The database defaults to the user name, same as libpq
+        dbname: config.get_dbname().or(config.get_user()),
+        user: config.get_user(),
+    }
+}
+
+/// Fills in the password from the password file, if the connection doesn't have one yet.
+pub(crate) fn fill_password(config: &mut Config) -> anyhow::Result<bool> {
+    if config.get_password().is_some() {
+        return Ok(false);
+    }
+    let path = pgpass_path().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
+    let entries = read_entries(&path)?;
 
+    match find_password(&entries, &target_of(config)) {
+        Some(password) => {
+            log::debug!("Using password from {}", path.display());
+            config.password(password);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn escape_field(field: &str) -> String {
+    field.replace('\\', "\\\\").replace(':', "\\:")
+}
+
+/// The user and password of each entry for `host:port`, e.g. of the credentials file `init` writes.
+pub(crate) fn read_user_passwords(
+    path: &Path,
+    host: &str,
+    port: u16,
+) -> anyhow::Result<Vec<(String, String)>> {
+    Ok(read_entries(path)?
+        .into_iter()
+        .filter(|entry| {
+            field_matches(&entry.host, Some(host))
+                && field_matches(&entry.port, Some(&port.to_string()))
+        })
+        .map(|entry| (entry.user, entry.password))
+        .collect())
+}
+
+/// Replaces the `host:port:*:user` entries of the given users, keeping the rest of the file as is.
+/// The new entries go where the first replaced one was, so they keep its precedence over wildcard entries.
+pub(crate) fn write_user_passwords(
+    path: &Path,
+    host: &str,
+    port: u16,
+    passwords: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    let existing = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let port = port.to_string();
+    let replaced = |line: &str| {
+        parse_pgpass_line(line).is_some_and(|entry| {
+            entry.host == host
+                && entry.port == port
+                && passwords.iter().any(|(user, _)| entry.user == *user)
+        })
+    };
+    let mut lines: Vec<String> = vec![];
+    let mut position = None;
+    for line in existing.lines() {
+        if replaced(line) {
+            position.get_or_insert(lines.len());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    let position = position.unwrap_or(lines.len());
+    // Roles are cluster-wide, so the entries match any DB
+    lines.splice(
+        position..position,
+        passwords.iter().map(|(user, password)| {
+            [host, &port, "*", user, password]
+                .map(escape_field)
+                .join(":")
+        }),
+    );
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(path)?;
+    file.write_all(format!("{}\n", lines.join("\n")).as_bytes())?;
+    Ok(())
+}
+
+/// The local default connection, only if the password file has a password for it.
+pub(crate) fn get_pg_config(
+    dbname: Option<String>,
+    username: Option<String>,
+    port: Option<u16>,
+) -> anyhow::Result<Config> {
+    let username = username
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or("postgres".to_string());
+    let dbname = dbname.unwrap_or_else(|| username.clone());
+    let host = env::var("PGHOST").unwrap_or_else(|_| "localhost".to_string());
+
+    let mut config = Config::new();
+    config
+        .host(&host)
+        .port(port.unwrap_or(DEFAULT_PORT))
+        .user(&username)
+        .dbname(&dbname);
+
+    if fill_password(&mut config)? {
+        Ok(config)
+    } else {
+        Err(anyhow::anyhow!(
+            "No password file entry for {}:{}:{}:{}",
+            host,
+            port.unwrap_or(DEFAULT_PORT),
+            dbname,
+            username
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
 
     #[test]
     fn test_parse_pgpass_line() {
-        let line = "localhost:5432:database:user:password";
-        let config = parse_pgpass_line(line).unwrap();
-        assert_eq!(config.get_hosts(), vec![Host::Tcp("localhost".to_string())]);
-        assert_eq!(config.get_ports(), vec![5432]);
-        assert_eq!(config.get_user(), Some("user"));
-        assert_eq!(config.get_dbname(), Some("database"));
-        assert_eq!(config.get_password(), Some(&b"password"[..]));
+        let entry = parse_pgpass_line("localhost:5432:database:user:password").unwrap();
+        assert_eq!(
+            entry,
+            PgpassEntry {
+                host: "localhost".to_string(),
+                port: "5432".to_string(),
+                dbname: "database".to_string(),
+                user: "user".to_string(),
+                password: "password".to_string(),
+            }
+        );
+
+        let escaped = parse_pgpass_line(r"db\:host:*:*:us\\er:pa\:ss\\word").unwrap();
+        assert_eq!(escaped.host, "db:host");
+        assert_eq!(escaped.user, r"us\er");
+        assert_eq!(escaped.password, r"pa:ss\word");
+
+        assert_eq!(parse_pgpass_line("# localhost:5432:db:user:pw"), None);
+        assert_eq!(parse_pgpass_line(""), None);
+        assert_eq!(parse_pgpass_line("localhost:5432:db:user"), None);
+    }
+
+    #[test]
+    fn test_matches_chosen_connection() {
+        let entries: Vec<PgpassEntry> = [
+            "# prod",
+            "prod.example.com:5432:app:app:prodsecret",
+            "localhost:5433:*:postgres:otherport",
+            "localhost:*:app:*:appsecret",
+            "*:*:*:postgres:fallback",
+        ]
+        .iter()
+        .filter_map(|line| parse_pgpass_line(line))
+        .collect();
+
+        let target = |host, port, dbname, user| Target {
+            host: Some(host),
+            port,
+            dbname: Some(dbname),
+            user: Some(user),
+        };
+
+        assert_eq!(
+            find_password(&entries, &target("localhost", 5432, "app", "app")),
+            Some("appsecret")
+        );
+        assert_eq!(
+            find_password(&entries, &target("prod.example.com", 5432, "app", "app")),
+            Some("prodsecret")
+        );
+        // First match wins
+        assert_eq!(
+            find_password(&entries, &target("localhost", 5433, "app", "postgres")),
+            Some("otherport")
+        );
+        assert_eq!(
+            find_password(&entries, &target("/var/run/postgresql", 5432, "app", "x")),
+            Some("appsecret")
+        );
+        assert_eq!(
+            find_password(&entries, &target("db.local", 5432, "other", "postgres")),
+            Some("fallback")
+        );
+        assert_eq!(
+            find_password(&entries, &target("db.local", 5432, "other", "app")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_user_passwords() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(".pgpass");
+        fs::write(
+            &path,
+            "# keep\nlocalhost:5432:*:schemamap:old\nprod:5432:*:schemamap:prod\n*:*:*:other:secret\n",
+        )
+        .unwrap();
+
+        write_user_passwords(&path, "localhost", 5432, &[("schemamap", "n:e\\w")]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# keep\nlocalhost:5432:*:schemamap:n\\:e\\\\w\nprod:5432:*:schemamap:prod\n*:*:*:other:secret\n"
+        );
+        assert_eq!(
+            read_user_passwords(&path, "localhost", 5432).unwrap(),
+            vec![
+                ("schemamap".to_string(), "n:e\\w".to_string()),
+                ("other".to_string(), "secret".to_string()),
+            ]
+        );
+        assert_eq!(
+            read_user_passwords(&path, "prod", 5432).unwrap(),
+            vec![
+                ("schemamap".to_string(), "prod".to_string()),
+                ("other".to_string(), "secret".to_string()),
+            ]
+        );
+        assert_eq!(
+            read_user_passwords(&path, "prod", 5433).unwrap(),
+            vec![("other".to_string(), "secret".to_string())]
+        );
+
+        // Without an entry to replace, they go last
+        write_user_passwords(&path, "localhost", 5433, &[("other", "new")]).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("*:*:*:other:secret\nlocalhost:5433:*:other:new\n"));
+    }
+
+    #[test]
+    fn test_target_defaults_dbname_to_user() {
+        let config: Config = "host=localhost user=app".parse().unwrap();
+        let target = target_of(&config);
+        assert_eq!(target.port, 5432);
+        assert_eq!(target.dbname, Some("app"));
     }
 }
@@ -1,3 +1,124 @@
-pub(crate) fn get_pg_config() -> anyhow::Result<tokio_postgres::Config> {
-    Err(anyhow::anyhow!("pgsync not yet supported"))
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::PgConfig;
+
+// As per: https://github.com/ankane/pgsync#setup
+#[derive(Debug, Deserialize)]
+struct PgsyncConfig {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+const DEFAULT_CONFIG_FILE: &str = ".pgsync.yml";
+
+fn parse_pgsync_config(file_path: &Path) -> anyhow::Result<PgsyncConfig> {
+    let config: PgsyncConfig = serde_yaml::from_str(&fs::read_to_string(file_path)?)?;
+    Ok(config)
+}
+
+/// `--pgsync-config` accepts either a path or a variant name, `dev` meaning `.pgsync-dev.yml`, like `pgsync --db dev`.
+fn config_file_name(variant: Option<&str>) -> PathBuf {
+    match variant {
+        None => PathBuf::from(DEFAULT_CONFIG_FILE),
+        Some(variant) if variant.ends_with(".yml") || variant.ends_with(".yaml") => {
+            PathBuf::from(variant)
+        }
+        Some(variant) => PathBuf::from(format!(".pgsync-{}.yml", variant)),
+    }
+}
+
+// pgsync searches the current directory and its parents
+fn find_config_file(cwd: &Path, file_name: &Path) -> Option<PathBuf> {
+    if file_name.is_absolute() {
+        return Some(file_name.to_path_buf()).filter(|p| p.exists());
+    }
+    cwd.ancestors()
+        .map(|dir| dir.join(file_name))
+        .find(|p| p.exists())
+}
+
+fn get_pg_config_from_pgsync(config: &PgsyncConfig) -> anyhow::Result<PgConfig> {
+    // Only ever use the destination, the source is usually a production DB
+    let to = match &config.to {
+        Some(to) if !to.trim().is_empty() => to.trim(),
+        _ => {
+            if config.from.is_some() {
+                log::warn!("pgsync config only has a `from:` database, refusing to use it");
+            }
+            return Err(anyhow::anyhow!("No `to:` database in pgsync config"));
+        }
+    };
+
+    if to.starts_with("$(") {
+        return Err(anyhow::anyhow!(
+            "pgsync `to: {}` uses command substitution, which is not supported, please provide the connection string via --conn",
+            to
+        ));
+    }
+
+    super::parse_conn_str(to)
+}
+
+pub(crate) fn get_pg_config(variant: Option<&str>) -> anyhow::Result<PgConfig> {
+    let cwd = std::env::current_dir()?;
+    let file_name = config_file_name(variant);
+
+    let file_path = find_config_file(&cwd, &file_name)
+        .ok_or_else(|| anyhow::anyhow!("No pgsync config {} found", file_name.display()))?;
+
+    log::debug!(
+        "Checking for pgsync Postgres config in {}",
+        file_path.display()
+    );
+
+    let pgsync_config = parse_pgsync_config(&file_path)?;
+    let config = get_pg_config_from_pgsync(&pgsync_config)?;
+
+    log::info!("Using pgsync `to:` database from {}", file_path.display());
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::config::Host;
+
+    use super::*;
+
+    #[test]
+    fn test_prefers_to_database() {
+        let config: PgsyncConfig = serde_yaml::from_str(
+            "from: postgres://prod.example.com:5432/app?sslmode=require\nto: postgres://localhost:5432/app_dev\nto_safe: true\nexclude:\n  - schema_migrations\n",
+        )
+        .unwrap();
+        let pg_config = get_pg_config_from_pgsync(&config).unwrap();
+        assert_eq!(
+            pg_config.get_hosts(),
+            vec![Host::Tcp("localhost".to_string())]
+        );
+        assert_eq!(pg_config.get_dbname(), Some("app_dev"));
+
+        let from_only: PgsyncConfig =
+            serde_yaml::from_str("from: postgres://prod.example.com:5432/app\n").unwrap();
+        assert!(get_pg_config_from_pgsync(&from_only).is_err());
+
+        let command: PgsyncConfig =
+            serde_yaml::from_str("to: $(heroku config:get DATABASE_URL)\n").unwrap();
+        assert!(get_pg_config_from_pgsync(&command).is_err());
+    }
+
+    #[test]
+    fn test_config_file_name() {
+        assert_eq!(config_file_name(None), PathBuf::from(".pgsync.yml"));
+        assert_eq!(
+            config_file_name(Some("staging")),
+            PathBuf::from(".pgsync-staging.yml")
+        );
+        assert_eq!(
+            config_file_name(Some("config/pgsync.yml")),
+            PathBuf::from("config/pgsync.yml")
+        );
+    }
 }
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::PgConfig;
+
+// As per: https://www.prisma.io/docs/orm/reference/prisma-schema-reference#datasource
+const SCHEMA_FILES: [&str; 2] = ["prisma/schema.prisma", "schema.prisma"];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Datasource {
+    provider: Option<String>,
+    url: Option<String>,
+    direct_url: Option<String>,
+}
+
+// `"postgres://..."` or `env("DATABASE_URL")`, possibly followed by a `//` comment
+fn eval_value(value: &str, env_map: &HashMap<String, String>) -> Option<String> {
+    let value = value.trim();
+    if let Some(rest) = value.strip_prefix("env(") {
+        let (name, _) = rest.split_once(')')?;
+        let name = name.trim().trim_matches('"');
+        return env_map.get(name).cloned().filter(|v| !v.is_empty());
+    }
+    let (literal, _) = value.strip_prefix('"')?.split_once('"')?;
+    Some(literal.to_string())
+}
+
+fn parse_datasource(schema: &str, env_map: &HashMap<String, String>) -> Option<Datasource> {
+    let mut lines = schema.lines().map(str::trim);
+    lines.find(|line| line.starts_with("datasource ") && line.ends_with('{'))?;
+
+    let mut datasource = Datasource::default();
+    for line in lines.take_while(|line| *line != "}") {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = eval_value(value, env_map);
+        match key.trim() {
+            "provider" => datasource.provider = value,
+            "url" => datasource.url = value,
+            "directUrl" => datasource.direct_url = value,
+            _ => {}
+        }
+    }
+    Some(datasource)
+}
+
+fn find_schema(cwd: &Path) -> Option<PathBuf> {
+    SCHEMA_FILES
+        .iter()
+        .map(|f| cwd.join(f))
+        .find(|p| p.exists())
+        .or_else(|| {
+            // Multi-file schemas keep the datasource in one of prisma/schema/*.prisma
+            std::fs::read_dir(cwd.join("prisma").join("schema"))
+                .ok()?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "prisma"))
+                .find(|p| {
+                    fs::read_to_string(p)
+                        .is_ok_and(|s| s.lines().any(|l| l.trim().starts_with("datasource ")))
+                })
+        })
+}
+
+fn get_pg_config_from_datasource(datasource: &Datasource) -> anyhow::Result<PgConfig> {
+    match datasource.provider.as_deref() {
+        Some("postgresql" | "postgres") => {}
+        Some(provider) => {
+            return Err(anyhow::anyhow!(
+                "Prisma datasource uses {}, not Postgres",
+                provider
+            ))
+        }
+        None => return Err(anyhow::anyhow!("Prisma datasource has no provider")),
+    }
+
+    // directUrl bypasses connection poolers like PgBouncer, which is what migrations use too
+    let url = datasource
+        .direct_url
+        .as_ref()
+        .or(datasource.url.as_ref())
+        .ok_or_else(|| anyhow::anyhow!("Prisma datasource url is not set"))?;
+    super::parse_conn_str(url)
+}
+
+pub(crate) fn get_pg_config(skip_dotenv: bool) -> anyhow::Result<PgConfig> {
+    let cwd = std::env::current_dir()?;
+    let file_path = find_schema(&cwd).ok_or_else(|| anyhow::anyhow!("No schema.prisma found"))?;
+
+    // The Prisma CLI also loads the .env next to the schema
+    let mut env_map = HashMap::new();
+    if let Some(schema_dir) = file_path.parent().filter(|dir| *dir != cwd) {
+        if !skip_dotenv {
+            env_map
+                .extend(super::env::read_dotenv_file(&schema_dir.join(".env")).unwrap_or_default());
+        }
+    }
+    env_map.extend(super::env::load_env(&cwd, skip_dotenv));
+
+    log::debug!("Checking for Prisma datasource in {}", file_path.display());
+    let datasource = parse_datasource(&fs::read_to_string(&file_path)?, &env_map)
+        .ok_or_else(|| anyhow::anyhow!("No datasource in {}", file_path.display()))?;
+    let config = get_pg_config_from_datasource(&datasource)?;
+    log::info!("Using Prisma datasource from {}", file_path.display());
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+generator client {
+  provider = "prisma-client-js"
+}
+
+datasource db {
+  provider  = "postgresql"
+  url       = env("DATABASE_URL") // pooled
+  directUrl = env("DIRECT_URL")
+  // shadowDatabaseUrl = "postgres://localhost/shadow"
+}
+
+model User {
+  id Int @id
+}
+"#;
+
+    #[test]
+    fn test_parse_datasource() {
+        let env_map = HashMap::from([(
+            "DATABASE_URL".to_string(),
+            "postgres://app@localhost:6432/app".to_string(),
+        )]);
+        let datasource = parse_datasource(SCHEMA, &env_map).unwrap();
+        assert_eq!(
+            datasource,
+            Datasource {
+                provider: Some("postgresql".to_string()),
+                url: Some("postgres://app@localhost:6432/app".to_string()),
+                direct_url: None,
+            }
+        );
+        assert_eq!(
+            get_pg_config_from_datasource(&datasource)
+                .unwrap()
+                .get_ports(),
+            &[6432]
+        );
+
+        let mut with_direct = env_map.clone();
+        with_direct.insert(
+            "DIRECT_URL".to_string(),
+            "postgres://app@localhost:5432/app".to_string(),
+        );
+        let datasource = parse_datasource(SCHEMA, &with_direct).unwrap();
+        assert_eq!(
+            get_pg_config_from_datasource(&datasource)
+                .unwrap()
+                .get_ports(),
+            &[5432]
+        );
+
+        let sqlite = parse_datasource(
+            "datasource db {\n  provider = \"sqlite\"\n  url = \"file:./dev.db\"\n}\n",
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert!(get_pg_config_from_datasource(&sqlite).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs;
+use tokio_postgres::Config;
+
+use super::{unquote, PgConfig};
+
+// As per: https://guides.rubyonrails.org/configuring.html#configuring-a-database
+const DATABASE_YML: &str = "config/database.yml";
+
+fn ruby_literal(expr: &str) -> Option<String> {
+    let expr = expr.trim();
+    if let Some(s) = unquote(expr) {
+        return Some(s.to_string());
+    }
+    expr.parse::<i64>().ok().map(|n| n.to_string())
+}
+
+/// Evaluates the ERB expressions database.yml files use in practice:
+/// `ENV["X"]`, `ENV.fetch("X")`, `ENV.fetch("X", default)`, `ENV.fetch("X") { default }` and `ENV["X"] || default`.
+fn eval_ruby(expr: &str, env_map: &HashMap<String, String>) -> Option<String> {
+    let expr = expr.trim();
+    let expr = expr
+        .strip_suffix(".to_i")
+        .or_else(|| expr.strip_suffix(".to_s"))
+        .unwrap_or(expr);
+    if let Some(literal) = ruby_literal(expr) {
+        return Some(literal);
+    }
+
+    let (name, inline_default, rest) = if let Some(rest) = expr.strip_prefix("ENV[") {
+        let (name, rest) = rest.split_once(']')?;
+        (unquote(name)?, None, rest)
+    } else if let Some(rest) = expr.strip_prefix("ENV.fetch(") {
+        let (args, rest) = rest.split_once(')')?;
+        match args.split_once(',') {
+            Some((name, default)) => (unquote(name)?, Some(default), rest),
+            None => (unquote(args)?, None, rest),
+        }
+    } else {
+        return None;
+    };
+
+    if let Some(value) = env_map.get(name) {
+        return Some(value.clone());
+    }
+    if let Some(default) = inline_default {
+        return eval_ruby(default, env_map);
+    }
+
+    let rest = rest.trim();
+    if let Some(default) = rest.strip_prefix("||") {
+        eval_ruby(default, env_map)
+    } else if let Some(block) = rest.strip_prefix('{').and_then(|b| b.strip_suffix('}')) {
+        // `{ |_name| default }`
+        let block = block.trim();
+        let block = match block.strip_prefix('|') {
+            Some(params) => params.split_once('|').map_or("", |(_, body)| body),
+            None => block,
+        };
+        eval_ruby(block, env_map)
+    } else {
+        None
+    }
+}
+
+fn render_erb(template: &str, env_map: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("<%") {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("%>") else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 2..start + end];
+        // Only `<%= %>` outputs anything, `<% %>` and `<%# %>` are dropped
+        if let Some(expr) = tag.strip_prefix('=') {
+            match eval_ruby(expr, env_map) {
+                Some(value) => output.push_str(&value),
+                None => log::debug!(
+                    "Unsupported ERB expression in database.yml: {}",
+                    expr.trim()
+                ),
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn is_postgres_adapter(db_config: &Value) -> bool {
+    db_config
+        .get("adapter")
+        .and_then(Value::as_str)
+        .is_some_and(|adapter| adapter.contains("postg"))
+}
+
+// Multi-database apps nest the configs, e.g. `development: primary: ...`
+fn pick_database(env_config: &Value) -> Option<&Value> {
+    if env_config.get("adapter").is_some() || env_config.get("url").is_some() {
+        return Some(env_config);
+    }
+    let mapping = env_config.as_mapping()?;
+    mapping
+        .get("primary")
+        .filter(|db| is_postgres_adapter(db))
+        .or_else(|| mapping.values().find(|db| is_postgres_adapter(db)))
+}
+
+fn get_pg_config_from_database_yml(
+    contents: &str,
+    rails_env: &str,
+    env_map: &HashMap<String, String>,
+) -> anyhow::Result<PgConfig> {
+    let mut doc: Value = serde_yaml::from_str(&render_erb(contents, env_map))?;
+    // `<<: *default`
+    doc.apply_merge()?;
+
+    let env_config = doc
+        .get(rails_env)
+        .ok_or_else(|| anyhow::anyhow!("No {} section in {}", rails_env, DATABASE_YML))?;
+    let db_config = pick_database(env_config)
+        .ok_or_else(|| anyhow::anyhow!("No Postgres database in the {} section", rails_env))?;
+
+    let get = |key: &str| match db_config.get(key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+
+    if let Some(url) = get("url") {
+        return super::parse_conn_str(&url);
+    }
+    if !is_postgres_adapter(db_config) {
+        return Err(anyhow::anyhow!(
+            "The {} database does not use the postgresql adapter",
+            rails_env
+        ));
+    }
+
+    let database = get("database")
+        .ok_or_else(|| anyhow::anyhow!("No database name in the {} section", rails_env))?;
+    let mut config = Config::new();
+    config
+        .host(get("host").as_deref().unwrap_or("localhost"))
+        .port(get("port").and_then(|p| p.parse().ok()).unwrap_or(5432))
+        .dbname(&database);
+    // libpq defaults to the OS user, same as Rails without a username
+    match get("username").or_else(|| env_map.get("USER").cloned()) {
+        Some(username) => config.user(&username),
+        None => config.user("postgres"),
+    };
+    if let Some(password) = get("password") {
+        config.password(password);
+    }
+    PgConfig::from_config(config)
+}
+
+pub(crate) fn get_pg_config(skip_dotenv: bool) -> anyhow::Result<PgConfig> {
+    let cwd = std::env::current_dir()?;
+    let file_path = cwd.join(DATABASE_YML);
+    if !file_path.exists() {
+        return Err(anyhow::anyhow!("No {} found", DATABASE_YML));
+    }
+
+    let env_map = super::env::load_env(&cwd, skip_dotenv);
+    let rails_env = env_map
+        .get("RAILS_ENV")
+        .or_else(|| env_map.get("RACK_ENV"))
+        .map_or("development", |e| e.as_str());
+
+    log::debug!(
+        "Checking for Rails {} database in {}",
+        rails_env,
+        file_path.display()
+    );
+    let config =
+        get_pg_config_from_database_yml(&fs::read_to_string(&file_path)?, rails_env, &env_map)?;
+    log::info!(
+        "Using Rails {} database from {}",
+        rails_env,
+        file_path.display()
+    );
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::config::Host;
+
+    use super::*;
+
+    const DATABASE_YML: &str = r#"
+default: &default
+  adapter: postgresql
+  encoding: unicode
+  pool: <%= ENV.fetch("RAILS_MAX_THREADS") { 5 } %>
+  host: <%= ENV.fetch("DB_HOST") { "localhost" } %>
+  port: <%= ENV.fetch("DB_PORT", 5432) %>
+  username: <%= ENV["DB_USER"] || "rails" %>
+  password: <%= ENV['DB_PASSWORD'] %>
+
+development:
+  <<: *default
+  database: app_development
+
+test:
+  <<: *default
+  database: app_test
+
+production:
+  primary:
+    <<: *default
+    database: app_production
+  cache:
+    <<: *default
+    database: app_production_cache
+"#;
+
+    #[test]
+    fn test_database_yml() {
+        let env_map = HashMap::from([
+            ("DB_PORT".to_string(), "5433".to_string()),
+            ("DB_PASSWORD".to_string(), "secret".to_string()),
+        ]);
+
+        let config =
+            get_pg_config_from_database_yml(DATABASE_YML, "development", &env_map).unwrap();
+        assert_eq!(config.get_hosts(), vec![Host::Tcp("localhost".to_string())]);
+        assert_eq!(config.get_ports(), vec![5433]);
+        assert_eq!(config.get_user(), Some("rails"));
+        assert_eq!(config.get_password(), Some(&b"secret"[..]));
+        assert_eq!(config.get_dbname(), Some("app_development"));
+
+        let production =
+            get_pg_config_from_database_yml(DATABASE_YML, "production", &env_map).unwrap();
+        assert_eq!(production.get_dbname(), Some("app_production"));
+
+        assert!(get_pg_config_from_database_yml(DATABASE_YML, "staging", &env_map).is_err());
+    }
+
+    #[test]
+    fn test_eval_ruby() {
+        let env_map = HashMap::from([("SET".to_string(), "value".to_string())]);
+        assert_eq!(
+            eval_ruby(r#"ENV["SET"]"#, &env_map),
+            Some("value".to_string())
+        );
+        assert_eq!(
+            eval_ruby(r#"ENV.fetch("UNSET") { |_| "fallback" }"#, &env_map),
+            Some("fallback".to_string())
+        );
+        assert_eq!(
+            eval_ruby(r#"ENV.fetch('UNSET', 5432).to_i"#, &env_map),
+            Some("5432".to_string())
+        );
+        assert_eq!(eval_ruby(r#"ENV["UNSET"]"#, &env_map), None);
+    }
+}
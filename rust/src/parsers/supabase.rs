@@ -1,49 +1,237 @@
-use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use tokio_postgres::Config;
 
-#[derive(Debug, Deserialize)]
+use super::PgConfig;
+use crate::tls::{SslMode, TlsConfig};
+
+// As per: https://supabase.com/docs/guides/local-development/cli/config
+#[derive(Debug, Deserialize, Default)]
 struct SupabaseConfig {
-    #[serde(rename = "db")]
-    database: DatabaseConfig,
+    project_id: Option<String>,
+    #[serde(default)]
+    db: DatabaseConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct DatabaseConfig {
-    #[serde(rename = "user")]
-    username: String,
-    password: String,
-    host: String,
-    port: u16,
-    dbname: String,
+    port: Option<u16>,
+    shadow_port: Option<u16>,
+    major_version: Option<u16>,
+}
+
+const DEFAULT_DB_PORT: u16 = 54322;
+const DEFAULT_SHADOW_PORT: u16 = 54320;
+
+// The local stack always uses the same credentials
+const LOCAL_USER: &str = "postgres";
+const LOCAL_PASSWORD: &str = "postgres";
+const LOCAL_DBNAME: &str = "postgres";
+
+// Same env var as `supabase link` and `supabase db push` use
+const SUPABASE_DB_PASSWORD_ENV: &str = "SUPABASE_DB_PASSWORD";
+
+/// Which database of a Supabase project to connect to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SupabaseDb {
+    /// The DB of `supabase start`
+    #[default]
+    Local,
+    /// The shadow DB used by `supabase db diff`
+    Shadow,
+    /// The remote project of `supabase link`
+    Linked,
+}
+
+impl std::fmt::Display for SupabaseDb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SupabaseDb::Local => "local",
+            SupabaseDb::Shadow => "shadow",
+            SupabaseDb::Linked => "linked",
+        };
+        f.write_str(s)
+    }
+}
+
+// The Supabase CLI looks for the supabase directory in the parent directories too
+fn find_supabase_dir(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join("supabase"))
+        .find(|dir| dir.join("config.toml").exists())
 }
 
 fn parse_supabase_config(file_path: &Path) -> anyhow::Result<SupabaseConfig> {
-    let mut file = File::open(file_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let config: SupabaseConfig = toml::from_str(&contents)?;
+    let config: SupabaseConfig = toml::from_str(&fs::read_to_string(file_path)?)?;
     Ok(config)
 }
 
-fn get_pg_config_from_supabase(config: &SupabaseConfig) -> Config {
+fn get_local_pg_config(config: &SupabaseConfig, db: SupabaseDb) -> Config {
+    let port = match db {
+        SupabaseDb::Shadow => config.db.shadow_port.unwrap_or(DEFAULT_SHADOW_PORT),
+        _ => config.db.port.unwrap_or(DEFAULT_DB_PORT),
+    };
+    if let Some(major_version) = config.db.major_version {
+        log::debug!("Supabase local Postgres major version: {}", major_version);
+    }
+
     let mut pg_config = Config::new();
     pg_config
-        .host(&config.database.host)
-        .port(config.database.port)
-        .user(&config.database.username)
-        .password(&config.database.password)
-        .dbname(&config.database.dbname);
+        .host("127.0.0.1")
+        .port(port)
+        .user(LOCAL_USER)
+        .password(LOCAL_PASSWORD)
+        .dbname(LOCAL_DBNAME);
     pg_config
 }
 
-pub(crate) fn get_pg_config() -> anyhow::Result<Config> {
+// Supabase only accepts TLS connections, unless told otherwise by PGSSLMODE
+fn linked_tls_defaults() -> anyhow::Result<TlsConfig> {
+    let mut tls = TlsConfig::from_env()?;
+    if env::var("PGSSLMODE").map_or(true, |mode| mode.is_empty()) {
+        tls.mode = SslMode::Require;
+    }
+    Ok(tls)
+}
+
+fn read_temp_file(supabase_dir: &Path, file_name: &str) -> Option<String> {
+    fs::read_to_string(supabase_dir.join(".temp").join(file_name))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// The pooler URL written by `supabase link`, or the direct connection of the linked project ref.
+fn get_linked_pg_config(supabase_dir: &Path) -> anyhow::Result<PgConfig> {
+    let conn_str = match read_temp_file(supabase_dir, "pooler-url") {
+        Some(pooler_url) => {
+            log::debug!("Using the linked Supabase project's pooler URL");
+            pooler_url
+        }
+        None => {
+            let project_ref = read_temp_file(supabase_dir, "project-ref").ok_or_else(|| {
+                anyhow::anyhow!("Supabase project is not linked, run `supabase link` first")
+            })?;
+            format!(
+                "postgresql://{}@db.{}.supabase.co:5432/{}",
+                LOCAL_USER, project_ref, LOCAL_DBNAME
+            )
+        }
+    };
+
+    let (rest, tls) = crate::tls::split_conn_str(&conn_str, linked_tls_defaults()?)?;
+    let mut config = PgConfig::new(rest.parse::<Config>()?, tls);
+
+    // The password is never written to disk by the Supabase CLI
+    if config.get_password().is_none() {
+        if let Ok(password) = env::var(SUPABASE_DB_PASSWORD_ENV) {
+            config.password(password);
+        } else {
+            log::debug!(
+                "No {} set for the linked Supabase project",
+                SUPABASE_DB_PASSWORD_ENV
+            );
+        }
+    }
+
+    Ok(config)
+}
+
+pub(crate) fn get_pg_config(db: SupabaseDb) -> anyhow::Result<PgConfig> {
     let cwd = std::env::current_dir()?;
-    let file_path = cwd.join("supabase/config.toml");
+    let supabase_dir =
+        find_supabase_dir(&cwd).ok_or_else(|| anyhow::anyhow!("No supabase/config.toml found"))?;
+    let file_path = supabase_dir.join("config.toml");
 
     let supabase_config = parse_supabase_config(&file_path)?;
+    log::info!(
+        "Using Supabase {} DB of project {} from {}",
+        db,
+        supabase_config.project_id.as_deref().unwrap_or("<unnamed>"),
+        file_path.display()
+    );
+
+    match db {
+        SupabaseDb::Local | SupabaseDb::Shadow => {
+            PgConfig::from_config(get_local_pg_config(&supabase_config, db))
+        }
+        SupabaseDb::Linked => get_linked_pg_config(&supabase_dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::config::Host;
+
+    use super::*;
+
+    const CONFIG_TOML: &str = r#"
+project_id = "my-app"
+
+[api]
+port = 54321
+
+[db]
+port = 54422
+shadow_port = 54420
+major_version = 15
+
+[studio]
+enabled = true
+"#;
+
+    #[test]
+    fn test_local_and_shadow_db() {
+        let config: SupabaseConfig = toml::from_str(CONFIG_TOML).unwrap();
+
+        let local = get_local_pg_config(&config, SupabaseDb::Local);
+        assert_eq!(local.get_hosts(), vec![Host::Tcp("127.0.0.1".to_string())]);
+        assert_eq!(local.get_ports(), vec![54422]);
+        assert_eq!(local.get_user(), Some("postgres"));
+        assert_eq!(local.get_password(), Some(&b"postgres"[..]));
+        assert_eq!(local.get_dbname(), Some("postgres"));
+
+        let shadow = get_local_pg_config(&config, SupabaseDb::Shadow);
+        assert_eq!(shadow.get_ports(), vec![54420]);
+
+        let defaults: SupabaseConfig = toml::from_str("project_id = \"x\"").unwrap();
+        assert_eq!(
+            get_local_pg_config(&defaults, SupabaseDb::Local).get_ports(),
+            vec![DEFAULT_DB_PORT]
+        );
+    }
+
+    #[test]
+    fn test_linked_pooler_url() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join(".temp")).unwrap();
+
+        assert!(get_linked_pg_config(dir).is_err());
+
+        std::fs::write(dir.join(".temp/project-ref"), "abcdefgh\n").unwrap();
+        let direct = get_linked_pg_config(dir).unwrap();
+        assert_eq!(
+            direct.get_hosts(),
+            vec![Host::Tcp("db.abcdefgh.supabase.co".to_string())]
+        );
 
-    Ok(get_pg_config_from_supabase(&supabase_config))
+        std::fs::write(
+            dir.join(".temp/pooler-url"),
+            "postgresql://postgres.abcdefgh@aws-0-eu-central-1.pooler.supabase.com:5432/postgres\n",
+        )
+        .unwrap();
+        let pooler = get_linked_pg_config(dir).unwrap();
+        assert_eq!(
+            pooler.get_hosts(),
+            vec![Host::Tcp(
+                "aws-0-eu-central-1.pooler.supabase.com".to_string()
+            )]
+        );
+        assert_eq!(pooler.get_user(), Some("postgres.abcdefgh"));
+    }
 }
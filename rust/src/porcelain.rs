@@ -1,9 +1,17 @@
-use clap::Parser;
-use tokio_postgres::{Client, Config};
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use console::style;
+use dialoguer::theme::ColorfulTheme;
+use serde::Serialize;
+use tokio_postgres::{error::SqlState, Client};
 
 use crate::{
-    common::{Cli, SCHEMAMAP_DEV_DB},
-    parsers,
+    common::Cli,
+    config::{self, Settings},
+    fingerprint::{self, SchemaFingerprint},
+    parsers::{self, ParamOrigin, PgConfig},
+    tls,
 };
 
 #[derive(Parser, Debug, Default, Clone)]
@@ -22,7 +30,7 @@ pub struct StatusArgs {
     #[arg(
       short('a'),
       long,
-      help = "Return all schemamap.columns records as a JSON array.",
+      help = "Return all columns records of the SDK schema as a JSON array.",
       default_missing_value = "true",
       default_value = "false",
 
@@ -32,12 +40,59 @@ pub struct StatusArgs {
     all: Option<bool>,
 }
 
-pub async fn connect_from_config(config: &Config) -> anyhow::Result<Client> {
-    let (client, connection) = match config.connect(tokio_postgres::NoTls).await {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("Failed to connect to database: {}", e);
-            return Err(anyhow::anyhow!("Failed to connect to database: {}", e));
+// Like psql, but with a few more tries
+const PASSWORD_ATTEMPTS: usize = 3;
+
+fn needs_password(e: &tokio_postgres::Error) -> bool {
+    e.code() == Some(&SqlState::INVALID_PASSWORD) || e.to_string().contains("password missing")
+}
+
+fn prompt_for_password(config: &PgConfig) -> anyhow::Result<String> {
+    let password = dialoguer::Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Password for user {}",
+            config.get_user().unwrap_or_default()
+        ))
+        .allow_empty_password(true)
+        .interact()?;
+    Ok(password)
+}
+
+/// Connects, prompting for the password when the server asks for one and `interactive` is set.
+/// On failure, logs where each connection parameter came from.
+pub(crate) async fn connect_with_prompt(
+    config: &mut PgConfig,
+    interactive: bool,
+) -> anyhow::Result<Client> {
+    let tls_connector = config.tls.make_connector()?;
+
+    let mut attempts = 0;
+    let (client, connection) = loop {
+        match config.connect(tls_connector.clone()).await {
+            Ok(c) => break c,
+            Err(e) if interactive && attempts < PASSWORD_ATTEMPTS && needs_password(&e) => {
+                log::warn!("{}", e);
+                attempts += 1;
+                let password = prompt_for_password(config)?;
+                config.password(password);
+                config.set_origin("password", ParamOrigin::Prompt);
+            }
+            Err(e) => {
+                let message = tls::explain_connect_error(&config.tls, &e);
+                log::error!("Failed to connect to database: {}", message);
+                log::error!("Connection parameters:\n{}", config.explain_origins());
+                let guessed = config.guessed();
+                if !guessed.is_empty() {
+                    log::error!(
+                        "No connection source provided {}, pass --conn or set DATABASE_URL or PG* env vars",
+                        guessed.join(", ")
+                    );
+                }
+                return Err(anyhow::anyhow!(
+                    "Failed to connect to database: {}",
+                    message
+                ));
+            }
         }
     };
 
@@ -50,25 +105,36 @@ pub async fn connect_from_config(config: &Config) -> anyhow::Result<Client> {
     Ok(client)
 }
 
+pub(crate) async fn connect_from_config(config: &mut PgConfig) -> anyhow::Result<Client> {
+    connect_with_prompt(config, atty::is(atty::Stream::Stdin)).await
+}
+
 pub async fn connect(cli: &Cli) -> anyhow::Result<Client> {
-    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    let mut pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
 
-    connect_from_config(&pgconfig).await
+    connect_from_config(&mut pgconfig).await
 }
 
-async fn refresh_sql(client: &Client) -> anyhow::Result<()> {
-    let refresh_sql = "select schemamap.update_schema_metadata_overview(concurrently := false)";
+async fn refresh_sql(client: &Client, schema: &str) -> anyhow::Result<()> {
+    let refresh_sql = format!(
+        "select {}.update_schema_metadata_overview(concurrently := false)",
+        schema
+    );
 
-    log::info!("Refreshing schemamap.schema_metadata_overview");
-    if let Err(e) = client.execute(refresh_sql, &[]).await {
+    log::info!("Refreshing {}.schema_metadata_overview", schema);
+    if let Err(e) = client.execute(&refresh_sql, &[]).await {
         log::warn!(
-            "Failed to refresh schemamap.schema_metadata_overview: {}",
+            "Failed to refresh {}.schema_metadata_overview: {}",
+            schema,
             e
         );
         return Err(e.into());
     }
 
-    log::info!("Refreshed schemamap.schema_metadata_overview with latest DB state");
+    log::info!(
+        "Refreshed {}.schema_metadata_overview with latest DB state",
+        schema
+    );
 
     Ok(())
 }
@@ -77,38 +143,49 @@ async fn refresh_sql(client: &Client) -> anyhow::Result<()> {
 pub struct RefreshArgs {}
 
 pub async fn refresh(cli: &Cli) -> anyhow::Result<()> {
+    let schema = Settings::resolve(cli)?.schema.value;
     let client = connect(cli).await?;
 
-    refresh_sql(&client).await?;
+    refresh_sql(&client, &schema).await?;
 
     Ok(())
 }
 
 pub async fn status(cli: &Cli, args: &StatusArgs) -> anyhow::Result<()> {
+    let schema = Settings::resolve(cli)?.schema.value;
     let client = connect(cli).await?;
 
     if let Some(refresh) = args.refresh {
         if refresh {
-            refresh_sql(&client).await?;
+            refresh_sql(&client, &schema).await?;
         }
     }
 
     let all = args.all.unwrap_or(false);
 
     let output = if all {
-        client.query_one(
-            "select jsonb_pretty(jsonb_agg(smo order by schema_name, table_name, column_name)) as smo_text
-                from schemamap.columns as smo",
-            &[],
-        )
+        client
+            .query_one(
+                &format!(
+                    "select jsonb_pretty(jsonb_agg(smo order by schema_name, table_name, column_name)) as smo_text
+                from {}.columns as smo",
+                    schema
+                ),
+                &[],
+            )
+            .await?
     } else {
-        client.query_one(
-            "select jsonb_pretty(to_jsonb(status)) as status_text
-            from schemamap.status as status",
-            &[],
-        )
-    }
-    .await?;
+        client
+            .query_one(
+                &format!(
+                    "select jsonb_pretty(to_jsonb(status)) as status_text
+            from {}.status as status",
+                    schema
+                ),
+                &[],
+            )
+            .await?
+    };
 
     println!(
         "{}",
@@ -121,30 +198,55 @@ pub async fn status(cli: &Cli, args: &StatusArgs) -> anyhow::Result<()> {
 pub async fn connect_to_schemamap_dev(cli: &Cli) -> anyhow::Result<Client> {
     let mut pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
 
-    pgconfig.dbname(SCHEMAMAP_DEV_DB);
+    pgconfig.dbname(&config::dev_db_name(cli)?);
 
-    connect_from_config(&pgconfig).await
+    connect_from_config(&mut pgconfig).await
 }
 
 #[derive(Parser, Debug, Default, Clone)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct SnapshotArgs {
+    #[command(subcommand)]
+    pub command: Option<SnapshotCommands>,
     #[arg(
         long("from"),
         help = "The name of the database to snapshot, defaulting to the DB of the connection string"
     )]
     pub template_db_name: Option<String>,
     #[arg(
-        help = "The name of the snapshot to create, defaults to DB name + current Git branch name"
+        help = "The name of the snapshot to create, defaults to DB name + current Git branch name (see snapshot.name_template in schemamap.toml)"
     )]
     pub snapshot_name: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Describe the snapshot, e.g. \"before migrating orders\""
+    )]
+    pub message: Option<String>,
+
+    #[arg(long, value_name = "TAG", help = "Tag the snapshot, can be repeated")]
+    pub tag: Vec<String>,
+}
+
+// For dev DBs created before the snapshot metadata columns existed
+const SNAPSHOT_METADATA_SQL: &str = "alter table snapshots
+  add column if not exists message text,
+  add column if not exists tags text[] not null default '{}',
+  add column if not exists schema_fingerprint text,
+  add column if not exists migration_version text;";
+
+async fn ensure_snapshot_metadata(client: &Client) -> anyhow::Result<()> {
+    client.batch_execute(SNAPSHOT_METADATA_SQL).await?;
+    Ok(())
 }
 
-struct GitStats {
-    branch_name: String,
-    revision: String,
+pub(crate) struct GitStats {
+    pub(crate) branch_name: String,
+    pub(crate) revision: String,
 }
 
-fn current_git_stats() -> anyhow::Result<GitStats> {
+pub(crate) fn current_git_stats() -> anyhow::Result<GitStats> {
     let repo = git2::Repository::discover(".")?;
     let head = repo.head()?;
     let branch_name = head
@@ -162,33 +264,355 @@ fn current_git_stats() -> anyhow::Result<GitStats> {
     })
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum SnapshotCommands {
+    #[command(about = "Drop snapshots and their databases")]
+    Drop(DropArgs),
+    #[command(
+        about = "Forget snapshots whose DB no longer exists, and drop the ones matching the retention policy"
+    )]
+    Gc(GcArgs),
+}
+
+#[derive(Parser, Debug, Default, Clone)]
+pub struct DropArgs {
+    #[arg(
+        required = true,
+        help = "The names of the snapshots to drop, see `schemamap list`"
+    )]
+    pub snapshot_names: Vec<String>,
+}
+
+#[derive(Parser, Debug, Default, Clone)]
+pub struct GcArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Keep the N newest snapshots of each DB, dropping the rest unless --older-than/--merged-branches are given too"
+    )]
+    pub keep_last: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "AGE",
+        value_parser = parse_age,
+        help = "Drop snapshots older than AGE, e.g. 12h, 14d or 2w"
+    )]
+    pub older_than: Option<Duration>,
+
+    #[arg(
+        long,
+        help = "Drop snapshots of Git branches that no longer exist locally",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    pub merged_branches: Option<bool>,
+
+    #[arg(
+        long,
+        value_name = "DB",
+        help = "Only collect the snapshots of this DB, instead of the one connected to"
+    )]
+    pub template: Option<String>,
+
+    #[arg(
+        long,
+        help = "Collect the snapshots of every DB, instead of the one connected to",
+        conflicts_with = "template",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    pub all: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Print the snapshots that would be dropped without dropping them",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    pub dry_run: Option<bool>,
+}
+
+fn parse_age(age: &str) -> anyhow::Result<Duration> {
+    let age = age.trim();
+    let unit_start = age.find(|c: char| !c.is_ascii_digit()).ok_or_else(|| {
+        anyhow::anyhow!("Missing unit in \"{}\", expected one of s, m, h, d, w", age)
+    })?;
+    let (amount, unit) = age.split_at(unit_start);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age \"{}\", expected e.g. 14d", age))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown unit \"{}\", expected one of s, m, h, d, w",
+                unit
+            ))
+        }
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+#[derive(Debug, Default, Serialize)]
+struct SnapshotInfo {
+    db_name: String,
+    template_db_name: String,
+    git_branch: Option<String>,
+    git_rev: Option<String>,
+    created_at: String,
+    message: Option<String>,
+    tags: Vec<String>,
+    schema_fingerprint: Option<String>,
+    migration_version: Option<String>,
+    #[serde(skip)]
+    age: Duration,
+    // None when the DB was dropped outside of `schemamap snapshot drop`
+    db_size_bytes: Option<i64>,
+    db_size_pretty: Option<String>,
+}
+
+/// Newest first
+async fn list_snapshots(client: &Client) -> anyhow::Result<Vec<SnapshotInfo>> {
+    ensure_snapshot_metadata(client).await?;
+
+    Ok(client
+        .query(
+            "select s.db_name, s.template_db_name, s.git_branch, s.git_rev,
+                    to_jsonb(s.created_at) #>> '{}',
+                    greatest(extract(epoch from now() - s.created_at), 0)::bigint,
+                    pg_database_size(d.oid),
+                    pg_size_pretty(pg_database_size(d.oid)),
+                    s.message, s.tags, s.schema_fingerprint, s.migration_version
+             from snapshots s
+             left join pg_database d on d.datname = s.db_name
+             order by s.created_at desc",
+            &[],
+        )
+        .await?
+        .iter()
+        .map(|row| SnapshotInfo {
+            db_name: row.get(0),
+            template_db_name: row.get(1),
+            git_branch: row.get(2),
+            git_rev: row.get(3),
+            created_at: row.get(4),
+            age: Duration::from_secs(row.get::<_, i64>(5) as u64),
+            db_size_bytes: row.get(6),
+            db_size_pretty: row.get(7),
+            message: row.get(8),
+            tags: row.get(9),
+            schema_fingerprint: row.get(10),
+            migration_version: row.get(11),
+        })
+        .collect())
+}
+
+/// Snapshots of `template`, or of every DB if None, whose DB was dropped outside of schemamap, like `gc_snapshots()`.
+/// Then `--older-than` and `--merged-branches` select what to drop, all snapshots if only `--keep-last` is given.
+/// The newest `--keep-last` snapshots of each DB are always kept.
+fn select_for_gc<'a>(
+    snapshots: &'a [SnapshotInfo],
+    args: &GcArgs,
+    template: Option<&str>,
+    branch_exists: impl Fn(&str) -> bool,
+) -> Vec<&'a SnapshotInfo> {
+    let (orphaned, existing): (Vec<_>, Vec<_>) = snapshots
+        .iter()
+        .filter(|s| template.is_none_or(|t| s.template_db_name == t))
+        .partition(|s| s.db_size_bytes.is_none());
+
+    let merged_branches = args.merged_branches.unwrap_or(false);
+    if args.keep_last.is_none() && args.older_than.is_none() && !merged_branches {
+        return orphaned;
+    }
+
+    let mut seen_per_db: std::collections::HashMap<&str, usize> = Default::default();
+    let selected = existing
+        .into_iter()
+        .filter(|s| {
+            let seen = seen_per_db.entry(&s.template_db_name).or_default();
+            *seen += 1;
+            args.keep_last.is_none_or(|n| *seen > n)
+        })
+        .filter(|s| {
+            if args.older_than.is_none() && !merged_branches {
+                return true;
+            }
+            let too_old = args.older_than.is_some_and(|age| s.age > age);
+            let merged = merged_branches
+                && s.git_branch
+                    .as_deref()
+                    .is_some_and(|b| b != "unknown" && !branch_exists(b));
+            too_old || merged
+        });
+    orphaned.into_iter().chain(selected).collect()
+}
+
+async fn drop_snapshots(cli: &Cli, args: &DropArgs) -> anyhow::Result<()> {
+    let client = connect_to_schemamap_dev(cli).await?;
+    let snapshots = list_snapshots(&client).await?;
+
+    // Only the DBs created by `schemamap snapshot`, so a typo can't drop the real one
+    for name in &args.snapshot_names {
+        if !snapshots.iter().any(|s| &s.db_name == name) {
+            return Err(anyhow::anyhow!(
+                "No snapshot named \"{}\", see `schemamap list`",
+                name
+            ));
+        }
+    }
+    for name in &args.snapshot_names {
+        log::info!("Dropping snapshot: {}", name);
+        client.execute("select drop_snapshot($1)", &[name]).await?;
+    }
+
+    Ok(())
+}
+
+async fn gc(cli: &Cli, args: &GcArgs) -> anyhow::Result<()> {
+    let dry_run = args.dry_run.unwrap_or(false);
+    let template = if args.all.unwrap_or(false) {
+        None
+    } else {
+        match &args.template {
+            Some(template) => Some(template.clone()),
+            None => Some(
+                parsers::parse_pgconfig_from_cli(cli)?
+                    .get_dbname()
+                    .unwrap_or("postgres")
+                    .to_string(),
+            ),
+        }
+    };
+    let client = connect_to_schemamap_dev(cli).await?;
+
+    let repo = if args.merged_branches.unwrap_or(false) {
+        Some(git2::Repository::discover(".").map_err(|e| {
+            anyhow::anyhow!(
+                "--merged-branches needs to be run in a Git repository: {}",
+                e
+            )
+        })?)
+    } else {
+        None
+    };
+    let branch_exists = |branch: &str| {
+        repo.as_ref()
+            .is_none_or(|r| r.find_branch(branch, git2::BranchType::Local).is_ok())
+    };
+
+    let snapshots = list_snapshots(&client).await?;
+    let selected = select_for_gc(&snapshots, args, template.as_deref(), branch_exists);
+
+    for s in &selected {
+        let branch = s.git_branch.as_deref().unwrap_or("unknown");
+        if dry_run {
+            println!("{}", s.db_name);
+        } else if s.db_size_bytes.is_none() {
+            log::info!("Forgetting snapshot: {} (DB already dropped)", s.db_name);
+            client
+                .execute("select drop_snapshot($1)", &[&s.db_name])
+                .await?;
+        } else {
+            log::info!("Dropping snapshot: {} (branch: {})", s.db_name, branch);
+            client
+                .execute("select drop_snapshot($1)", &[&s.db_name])
+                .await?;
+        }
+    }
+    if !dry_run {
+        log::info!("Dropped {} snapshot(s)", selected.len());
+    }
+
+    Ok(())
+}
+
+async fn template_fingerprint(
+    cli: &Cli,
+    pgconfig: &PgConfig,
+    db_name: &str,
+) -> anyhow::Result<SchemaFingerprint> {
+    let schema = Settings::resolve(cli)?.schema.value;
+    let mut template_pgconfig = pgconfig.clone();
+    template_pgconfig.dbname(db_name);
+
+    let client = connect_from_config(&mut template_pgconfig).await?;
+    fingerprint::schema_fingerprint(&client, &schema).await
+}
+
 pub async fn snapshot(cli: &Cli, args: &SnapshotArgs) -> anyhow::Result<()> {
+    match &args.command {
+        Some(SnapshotCommands::Drop(drop_args)) => return drop_snapshots(cli, drop_args).await,
+        Some(SnapshotCommands::Gc(gc_args)) => return gc(cli, gc_args).await,
+        None => {}
+    }
+
+    let git_stats = current_git_stats().unwrap_or_else(|_| GitStats {
+        branch_name: "unknown".to_string(),
+        revision: "unknown".to_string(),
+    });
+    create_snapshot(cli, args, &git_stats).await?;
+
+    Ok(())
+}
+
+/// Snapshots the DB as of `git_stats`, which is the branch being left when called from a Git hook.
+/// Returns the name of the snapshot.
+pub(crate) async fn create_snapshot(
+    cli: &Cli,
+    args: &SnapshotArgs,
+    git_stats: &GitStats,
+) -> anyhow::Result<String> {
     let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
 
     let mut dev_pgconfig = pgconfig.clone();
-    dev_pgconfig.dbname(SCHEMAMAP_DEV_DB);
+    dev_pgconfig.dbname(&config::dev_db_name(cli)?);
 
-    let client = connect_from_config(&dev_pgconfig).await?;
+    let client = connect_from_config(&mut dev_pgconfig).await?;
 
     let template_db_name = args.template_db_name.as_ref().map_or_else(
-        || {
-            pgconfig
-                .get_dbname()
-                .unwrap_or_else(|| "postgres")
-                .to_string()
-        },
+        || pgconfig.get_dbname().unwrap_or("postgres").to_string(),
         |name| name.clone(),
     );
-    let git_stats = current_git_stats().unwrap_or_else(|_| GitStats {
-        branch_name: "unknown".to_string(),
-        revision: "unknown".to_string(),
-    });
 
+    let name_template = Settings::resolve(cli)?.snapshot_name_template.value;
     let new_db_name = args.snapshot_name.as_ref().map_or_else(
-        || format!("{}_{}", template_db_name, git_stats.branch_name),
+        || {
+            config::render_snapshot_name(
+                &name_template,
+                &template_db_name,
+                &git_stats.branch_name,
+                &git_stats.revision,
+            )
+        },
         |name| name.clone(),
     );
 
+    // Before copying it, as create_snapshot kills the connections to the template DB
+    let fingerprint = template_fingerprint(cli, &pgconfig, &template_db_name)
+        .await
+        .inspect_err(|e| {
+            log::warn!(
+                "Failed to fingerprint the schema of {}, `schemamap list` won't know which snapshots are compatible with it: {}",
+                template_db_name,
+                e
+            )
+        })
+        .ok();
+
+    ensure_snapshot_metadata(&client).await?;
+
     client
         .execute(
             "select create_snapshot($1, $2)",
@@ -198,12 +622,25 @@ pub async fn snapshot(cli: &Cli, args: &SnapshotArgs) -> anyhow::Result<()> {
 
     client
         .execute(
-            "update snapshots set git_branch = $1, git_rev = $2 where db_name = $3",
-            &[&git_stats.branch_name, &git_stats.revision, &new_db_name],
+            "update snapshots
+             set git_branch = $1, git_rev = $2, message = $3, tags = $4,
+                 schema_fingerprint = $5, migration_version = $6
+             where db_name = $7",
+            &[
+                &git_stats.branch_name,
+                &git_stats.revision,
+                &args.message,
+                &args.tag,
+                &fingerprint.as_ref().map(|f| &f.fingerprint),
+                &fingerprint
+                    .as_ref()
+                    .and_then(|f| f.migration_version.as_ref()),
+                &new_db_name,
+            ],
         )
         .await?;
 
-    Ok(())
+    Ok(new_db_name)
 }
 
 #[derive(Parser, Debug, Default, Clone)]
@@ -214,78 +651,591 @@ pub struct RestoreArgs {
     )]
     pub new_db_name: Option<String>,
     #[arg(
-        help = "The name of the snapshot to restore from, defaults to DB name + current Git branch name"
+        help = "The name of the snapshot to restore from, defaults to DB name + current Git branch name (see snapshot.name_template in schemamap.toml)"
     )]
     pub snapshot_name: Option<String>,
-}
 
-pub async fn restore(cli: &Cli, args: &RestoreArgs) -> anyhow::Result<()> {
-    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    #[arg(
+        long,
+        help = "Ask for confirmation before replacing the DB",
+        default_missing_value = "true",
+        default_value = "true",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+    )]
+    pub(crate) input: Option<bool>,
 
-    let mut dev_pgconfig = pgconfig.clone();
-    dev_pgconfig.dbname(SCHEMAMAP_DEV_DB);
+    #[arg(
+        long,
+        help = "Snapshot the DB to <db>_autosave before replacing it, and restore that if the restore fails",
+        default_missing_value = "true",
+        default_value = "true",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+    )]
+    pub(crate) autosave: Option<bool>,
+}
 
-    let client = connect_from_config(&dev_pgconfig).await?;
+fn autosave_name(db_name: &str) -> String {
+    format!("{}_autosave", db_name)
+}
 
-    let target_db_name = pgconfig.get_dbname().unwrap_or_else(|| "postgres");
+pub(crate) async fn snapshot_exists(client: &Client, snapshot_name: &str) -> anyhow::Result<bool> {
+    Ok(client
+        .query_one(
+            "select exists(
+               select 1 from snapshots s join pg_database d on d.datname = s.db_name
+               where s.db_name = $1)",
+            &[&snapshot_name],
+        )
+        .await?
+        .get(0))
+}
 
-    let snapshot_name = args.snapshot_name.as_ref().map_or_else(
-        || {
-            current_git_stats()
-                .map(|stats| format!("{}_{}", target_db_name, stats.branch_name))
-                .unwrap_or_else(|_| "postgres".to_string())
-                .to_string()
-        },
-        |name| name.clone(),
-    );
+async fn database_exists(client: &Client, db_name: &str) -> anyhow::Result<bool> {
+    Ok(client
+        .query_one(
+            "select exists(select 1 from pg_database where datname = $1)",
+            &[&db_name],
+        )
+        .await?
+        .get(0))
+}
 
-    let new_db_name = args
-        .new_db_name
-        .as_ref()
-        .map_or_else(|| target_db_name.to_string(), |name| name.clone());
+fn confirm_restore(db_name: &str, snapshot_name: &str) -> bool {
+    dialoguer::Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Replace the \"{}\" DB with the \"{}\" snapshot?",
+            db_name, snapshot_name
+        ))
+        .default(false)
+        .interact()
+        .unwrap_or(false)
+}
 
+async fn drop_and_restore(
+    client: &Client,
+    snapshot_name: &str,
+    new_db_name: &str,
+) -> anyhow::Result<()> {
     log::info!("Dropping DB: {}", new_db_name);
-
     client
         .execute("select drop_database($1)", &[&new_db_name])
         .await?;
 
     log::info!("Restoring: {} from: {}", new_db_name, snapshot_name);
-
     client
         .execute(
             "select restore_snapshot($1, $2)",
             &[&snapshot_name, &new_db_name],
         )
         .await?;
+    Ok(())
+}
+
+pub async fn restore(cli: &Cli, args: &RestoreArgs) -> anyhow::Result<()> {
+    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+
+    let mut dev_pgconfig = pgconfig.clone();
+    dev_pgconfig.dbname(&config::dev_db_name(cli)?);
+
+    let client = connect_from_config(&mut dev_pgconfig).await?;
+
+    let target_db_name = pgconfig.get_dbname().unwrap_or("postgres");
+
+    let name_template = Settings::resolve(cli)?.snapshot_name_template.value;
+    let snapshot_name = match &args.snapshot_name {
+        Some(name) => name.clone(),
+        None => {
+            let stats = current_git_stats().map_err(|e| {
+                anyhow::anyhow!(
+                    "Unable to determine the snapshot of the current Git branch, pass its name explicitly: {}",
+                    e
+                )
+            })?;
+            config::render_snapshot_name(
+                &name_template,
+                target_db_name,
+                &stats.branch_name,
+                &stats.revision,
+            )
+        }
+    };
+
+    let new_db_name = args
+        .new_db_name
+        .as_ref()
+        .map_or_else(|| target_db_name.to_string(), |name| name.clone());
+
+    if snapshot_name == new_db_name {
+        return Err(anyhow::anyhow!(
+            "Can't restore \"{}\" from itself",
+            new_db_name
+        ));
+    }
+    ensure_snapshot_metadata(&client).await?;
+    if !snapshot_exists(&client, &snapshot_name).await? {
+        return Err(anyhow::anyhow!(
+            "No snapshot named \"{}\", see `schemamap list`",
+            snapshot_name
+        ));
+    }
+
+    let db_exists = database_exists(&client, &new_db_name).await?;
+    let interactive = args.input.unwrap_or(true)
+        && atty::is(atty::Stream::Stdout)
+        && atty::is(atty::Stream::Stdin);
+    if db_exists && interactive && !confirm_restore(&new_db_name, &snapshot_name) {
+        return Err(anyhow::anyhow!("Restore cancelled"));
+    }
+
+    // Restoring the autosave itself is the undo, the snapshot stays intact either way
+    let autosave = autosave_name(&new_db_name);
+    let autosaved = db_exists && args.autosave.unwrap_or(true) && snapshot_name != autosave;
+    if autosaved {
+        log::info!(
+            "Saving the current state of {} to: {}",
+            new_db_name,
+            autosave
+        );
+        client
+            .execute("select create_snapshot($1, $2)", &[&new_db_name, &autosave])
+            .await?;
+        client
+            .execute(
+                "update snapshots set message = $1 where db_name = $2",
+                &[&format!("Before restoring {}", snapshot_name), &autosave],
+            )
+            .await?;
+    }
+
+    if let Err(e) = drop_and_restore(&client, &snapshot_name, &new_db_name).await {
+        if !autosaved {
+            return Err(e);
+        }
+        log::warn!(
+            "Restoring {} from {} failed, rolling back to: {}",
+            new_db_name,
+            snapshot_name,
+            autosave
+        );
+        drop_and_restore(&client, &autosave, &new_db_name)
+            .await
+            .map_err(|rollback| {
+                anyhow::anyhow!(
+                    "{}, and rolling back to {} failed too: {}",
+                    e,
+                    autosave,
+                    rollback
+                )
+            })?;
+        return Err(e.context(format!(
+            "Restore failed, {} was rolled back to its state before the restore",
+            new_db_name
+        )));
+    }
 
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
 #[derive(Parser, Debug, Default, Clone)]
-pub struct ListArgs {}
+pub struct ListArgs {
+    #[arg(
+        long,
+        value_enum,
+        help = "Output format [default: table on a terminal, json otherwise]"
+    )]
+    format: Option<ListFormat>,
 
-pub async fn list(cli: &Cli, _args: &ListArgs) -> anyhow::Result<()> {
-    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    #[arg(
+        long,
+        help = "Same as --format json",
+        conflicts_with = "format",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set
+    )]
+    json: Option<bool>,
 
-    let mut dev_pgconfig = pgconfig.clone();
-    dev_pgconfig.dbname(SCHEMAMAP_DEV_DB);
+    #[arg(long, help = "Only list the snapshots taken on this Git branch")]
+    branch: Option<String>,
 
-    let client = connect_from_config(&dev_pgconfig).await?;
+    #[arg(long, help = "Only list the snapshots of this DB")]
+    template: Option<String>,
 
-    let statement =
-        "select jsonb_pretty(jsonb_agg(snapshots order by created_at desc)) as snapshot_summary
-         from (select s.*,
-               pg_database_size(s.db_name) as db_size_bytes,
-               pg_size_pretty(pg_database_size(s.db_name)) as db_size_pretty
-    from snapshots s) snapshots";
+    #[arg(long, help = "Only list the snapshots with this tag")]
+    tag: Option<String>,
 
-    let output = client.query_one(statement, &[]).await?;
+    #[arg(
+        long,
+        value_name = "AGE",
+        value_parser = parse_age,
+        help = "Only list the snapshots taken in the last AGE, e.g. 12h, 14d or 2w"
+    )]
+    since: Option<Duration>,
+}
 
-    println!(
-        "{}",
-        output.try_get::<_, String>(0).unwrap_or("[]".to_string())
-    );
+#[derive(Serialize)]
+struct ListedSnapshot<'a> {
+    #[serde(flatten)]
+    snapshot: &'a SnapshotInfo,
+    /// Taken on the checked out Git branch
+    current_branch: bool,
+    /// Taken at the checked out Git revision
+    at_head: bool,
+    /// Same schema fingerprint as the DB of the connection string, None if either is unknown
+    schema_compatible: Option<bool>,
+}
+
+impl ListedSnapshot<'_> {
+    fn marker(&self) -> &'static str {
+        match (self.current_branch, self.at_head) {
+            (true, true) => "*",
+            (true, false) => "~",
+            _ => "",
+        }
+    }
+
+    fn schema_status(&self) -> &'static str {
+        match self.schema_compatible {
+            Some(true) => "same",
+            Some(false) => "differs",
+            None => "",
+        }
+    }
+
+    fn short_rev(&self) -> &str {
+        let rev = self.snapshot.git_rev.as_deref().unwrap_or_default();
+        rev.get(..7).unwrap_or(rev)
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        86400..=1209599 => format!("{}d ago", secs / 86400),
+        _ => format!("{}w ago", secs / 604800),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn print_csv(snapshots: &[ListedSnapshot]) {
+    println!("db_name,template_db_name,git_branch,git_rev,created_at,db_size_bytes,message,tags,schema_fingerprint,migration_version,current_branch,at_head,schema_compatible");
+    for s in snapshots {
+        let fields = [
+            s.snapshot.db_name.clone(),
+            s.snapshot.template_db_name.clone(),
+            s.snapshot.git_branch.clone().unwrap_or_default(),
+            s.snapshot.git_rev.clone().unwrap_or_default(),
+            s.snapshot.created_at.clone(),
+            s.snapshot
+                .db_size_bytes
+                .map(|b| b.to_string())
+                .unwrap_or_default(),
+            s.snapshot.message.clone().unwrap_or_default(),
+            s.snapshot.tags.join(" "),
+            s.snapshot.schema_fingerprint.clone().unwrap_or_default(),
+            s.snapshot.migration_version.clone().unwrap_or_default(),
+            s.current_branch.to_string(),
+            s.at_head.to_string(),
+            s.schema_compatible
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+        ];
+        println!(
+            "{}",
+            fields
+                .iter()
+                .map(|f| csv_field(f))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+}
+
+fn print_table(snapshots: &[ListedSnapshot]) {
+    if snapshots.is_empty() {
+        println!("No snapshots, create one with `schemamap snapshot`");
+        return;
+    }
+
+    let header = [
+        "", "NAME", "TEMPLATE", "BRANCH", "REV", "CREATED", "SIZE", "SCHEMA", "TAGS", "MESSAGE",
+    ];
+    let rows: Vec<[String; 10]> = snapshots
+        .iter()
+        .map(|s| {
+            [
+                s.marker().to_string(),
+                s.snapshot.db_name.clone(),
+                s.snapshot.template_db_name.clone(),
+                s.snapshot.git_branch.clone().unwrap_or_default(),
+                s.short_rev().to_string(),
+                format_age(s.snapshot.age),
+                s.snapshot
+                    .db_size_pretty
+                    .clone()
+                    .unwrap_or_else(|| "missing".to_string()),
+                s.schema_status().to_string(),
+                s.snapshot.tags.join(","),
+                s.snapshot.message.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: [&str; 10]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", style(line(header)).bold());
+    for (row, s) in rows.iter().zip(snapshots) {
+        let text = line(row.each_ref().map(String::as_str));
+        if s.at_head {
+            println!("{}", style(text).green().bold());
+        } else if s.snapshot.db_size_pretty.is_none() {
+            println!("{}", style(text).dim());
+        } else {
+            println!("{}", text);
+        }
+    }
+    if snapshots.iter().any(|s| s.current_branch) {
+        println!();
+        println!("* taken at the current Git HEAD, ~ on the current Git branch");
+    }
+    if snapshots.iter().any(|s| s.schema_compatible.is_some()) {
+        println!("SCHEMA: same tables, columns and indexes as the current DB, or differs and needs migrations after a restore");
+    }
+}
+
+pub async fn list(cli: &Cli, args: &ListArgs) -> anyhow::Result<()> {
+    let client = connect_to_schemamap_dev(cli).await?;
+
+    let snapshots = list_snapshots(&client).await?;
+    let git_stats = current_git_stats().ok();
+
+    let pgconfig = parsers::parse_pgconfig_from_cli(cli)?;
+    let current_db_name = pgconfig.get_dbname().unwrap_or("postgres");
+    let current_fingerprint = template_fingerprint(cli, &pgconfig, current_db_name)
+        .await
+        .inspect_err(|e| log::debug!("Failed to fingerprint the current schema: {}", e))
+        .ok();
+
+    let listed: Vec<ListedSnapshot> = snapshots
+        .iter()
+        .filter(|s| {
+            args.branch
+                .as_ref()
+                .is_none_or(|b| s.git_branch.as_ref() == Some(b))
+        })
+        .filter(|s| {
+            args.template
+                .as_ref()
+                .is_none_or(|t| &s.template_db_name == t)
+        })
+        .filter(|s| args.since.is_none_or(|since| s.age <= since))
+        .filter(|s| args.tag.as_ref().is_none_or(|t| s.tags.contains(t)))
+        .map(|s| {
+            // Snapshots of other DBs aren't what a checkout on this branch would restore
+            let current_branch = s.template_db_name == current_db_name
+                && git_stats
+                    .as_ref()
+                    .is_some_and(|g| s.git_branch.as_ref() == Some(&g.branch_name));
+            ListedSnapshot {
+                snapshot: s,
+                current_branch,
+                at_head: current_branch
+                    && git_stats
+                        .as_ref()
+                        .is_some_and(|g| s.git_rev.as_ref() == Some(&g.revision)),
+                schema_compatible: current_fingerprint.as_ref().and_then(|current| {
+                    s.schema_fingerprint
+                        .as_ref()
+                        .map(|f| f == &current.fingerprint)
+                }),
+            }
+        })
+        .collect();
+
+    let format = if args.json.unwrap_or(false) {
+        ListFormat::Json
+    } else {
+        args.format.unwrap_or(if atty::is(atty::Stream::Stdout) {
+            ListFormat::Table
+        } else {
+            ListFormat::Json
+        })
+    };
+    match format {
+        ListFormat::Table => print_table(&listed),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&listed)?),
+        ListFormat::Csv => print_csv(&listed),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(db_name: &str, template: &str, branch: &str, age_days: u64) -> SnapshotInfo {
+        SnapshotInfo {
+            db_name: db_name.to_string(),
+            template_db_name: template.to_string(),
+            git_branch: Some(branch.to_string()),
+            age: Duration::from_secs(age_days * 24 * 60 * 60),
+            db_size_bytes: Some(8 * 1024 * 1024),
+            ..Default::default()
+        }
+    }
+
+    fn names(selected: Vec<&SnapshotInfo>) -> Vec<&str> {
+        selected.iter().map(|s| s.db_name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_list_formatting() {
+        assert_eq!(format_age(Duration::from_secs(5)), "just now");
+        assert_eq!(format_age(Duration::from_secs(90 * 60)), "1h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400)), "3d ago");
+        assert_eq!(format_age(Duration::from_secs(30 * 86400)), "4w ago");
+
+        assert_eq!(csv_field("app_main"), "app_main");
+        assert_eq!(csv_field("fix,\"quotes\""), "\"fix,\"\"quotes\"\"\"");
+
+        let info = SnapshotInfo {
+            git_rev: Some("0123456789abcdef".to_string()),
+            ..snapshot("app_main", "app", "main", 1)
+        };
+        let listed = ListedSnapshot {
+            snapshot: &info,
+            current_branch: true,
+            at_head: false,
+            schema_compatible: Some(false),
+        };
+        assert_eq!(listed.marker(), "~");
+        assert_eq!(listed.schema_status(), "differs");
+        assert_eq!(listed.short_rev(), "0123456");
+    }
+
+    #[test]
+    fn test_select_for_gc() {
+        assert_eq!(parse_age("14d").unwrap(), Duration::from_secs(14 * 86400));
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert!(parse_age("14").is_err());
+        assert!(parse_age("2y").is_err());
+        assert!(parse_age("d").is_err());
+
+        let snapshots = vec![
+            snapshot("app_main", "app", "main", 1),
+            snapshot("app_feature", "app", "feature", 20),
+            snapshot("other_main", "other", "main", 30),
+            snapshot("app_old", "app", "unknown", 40),
+        ];
+        let branch_exists = |b: &str| b == "main";
+
+        assert!(select_for_gc(&snapshots, &GcArgs::default(), None, branch_exists).is_empty());
+
+        let keep_last = GcArgs {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(select_for_gc(&snapshots, &keep_last, None, branch_exists)),
+            vec!["app_feature", "app_old"]
+        );
+
+        let older_than = GcArgs {
+            older_than: Some(Duration::from_secs(14 * 86400)),
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(select_for_gc(&snapshots, &older_than, None, branch_exists)),
+            vec!["app_old"]
+        );
+
+        let merged = GcArgs {
+            merged_branches: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(select_for_gc(&snapshots, &merged, None, branch_exists)),
+            vec!["app_feature"]
+        );
+        assert_eq!(
+            names(select_for_gc(
+                &snapshots,
+                &keep_last,
+                Some("other"),
+                branch_exists
+            )),
+            Vec::<&str>::new()
+        );
+
+        // Their DB is gone, so they go regardless of the policies and don't count towards --keep-last
+        let mut with_orphans = snapshots;
+        with_orphans.insert(
+            0,
+            SnapshotInfo {
+                db_size_bytes: None,
+                ..snapshot("app_dropped", "app", "main", 0)
+            },
+        );
+        with_orphans.push(SnapshotInfo {
+            db_size_bytes: None,
+            ..snapshot("other_dropped", "other", "main", 50)
+        });
+        assert_eq!(
+            names(select_for_gc(
+                &with_orphans,
+                &GcArgs::default(),
+                Some("app"),
+                branch_exists
+            )),
+            vec!["app_dropped"]
+        );
+        assert_eq!(
+            names(select_for_gc(
+                &with_orphans,
+                &keep_last,
+                None,
+                branch_exists
+            )),
+            vec!["app_dropped", "other_dropped", "app_feature", "app_old"]
+        );
+    }
+}
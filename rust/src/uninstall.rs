@@ -0,0 +1,207 @@
+use anyhow::Result;
+use clap::Args;
+use dialoguer::theme::ColorfulTheme;
+use tokio_postgres::{Client, Config};
+
+use crate::common::Cli;
+use crate::config::Settings;
+use crate::init::{self, initialize_pgconfig};
+use crate::migrations::in_schema;
+use crate::porcelain;
+
+const UNINSTALL_SCHEMAMAP_SQL: &str = include_str!("../uninstall_schemamap.sql");
+const DROP_SCHEMA_SQL: &str = "drop schema if exists schemamap cascade;";
+
+// Goes inside the transaction of the uninstall script, so rows inserted after the check can't get dropped
+// and raising rolls back the drops. Also guards the --dry-run output piped into psql.
+const DATA_MIGRATIONS_GUARD_SQL: &str = "do $$
+begin
+  if to_regclass('schemamap.data_migrations') is not null then
+    if exists (select 1 from schemamap.data_migrations) then
+      raise exception 'schemamap.data_migrations has rows, refusing to drop the imported data. Use --force to uninstall anyway.';
+    end if;
+  end if;
+end $$;";
+
+#[derive(Args)]
+pub struct UninstallArgs {
+    #[arg(
+        long,
+        help = "Ask for inputs if not provided",
+        default_missing_value = "true",
+        default_value = "true",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+    )]
+    input: Option<bool>,
+
+    #[arg(long,
+        help = "Also drop the development-time DB with the snapshot/restore extensions",
+        default_missing_value = "true",
+        num_args =0..=1,
+        action = clap::ArgAction::Set
+    )]
+    dev: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Uninstall even if schemamap.data_migrations has rows, dropping the imported data",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    force: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Print SQL statements without executing them",
+        default_missing_value = "true",
+        default_value = "false",
+        num_args =0..=1,
+        action = clap::ArgAction::Set,
+      )]
+    pub(crate) dry_run: Option<bool>,
+}
+
+async fn remove_schemamap(
+    pgconfig: &Config,
+    schema: &str,
+    client: &Option<Client>,
+    force: bool,
+) -> Result<()> {
+    // NOTE: same as in `init::grant_schemamap_usage`, for Supabase via Supavisor/PGBouncer
+    let current_user =
+        init::normalize_username(pgconfig, pgconfig.get_user().unwrap_or("postgres"));
+
+    // Only the drop refers to the schema, the rest is about the roles named `schemamap*`
+    let mut uninstall_sql = UNINSTALL_SCHEMAMAP_SQL
+        .replace(
+            DROP_SCHEMA_SQL,
+            &format!("drop schema if exists {} cascade;", schema),
+        )
+        .replace(" CURRENT_USER;", format!(" \"{}\";", current_user).as_str());
+    if !force {
+        uninstall_sql = uninstall_sql.replacen(
+            "begin;\n",
+            &format!(
+                "begin;\n\n{}\n",
+                in_schema(DATA_MIGRATIONS_GUARD_SQL, schema)
+            ),
+            1,
+        );
+    }
+
+    if let Some(c) = client {
+        c.batch_execute(&uninstall_sql).await?;
+
+        let remaining: Vec<String> = c
+            .query(
+                "select rolname::text from pg_roles where rolname in ('schemamap', 'schemamap_readwrite', 'schemamap_readonly', 'schemamap_schema_read')",
+                &[],
+            )
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        if !remaining.is_empty() {
+            log::warn!(
+                "Roles {} are still used by other SDK schemas or databases, run schemamap uninstall there too",
+                remaining.join(", ")
+            );
+        }
+    } else {
+        println!("{}", uninstall_sql);
+    }
+    Ok(())
+}
+
+async fn drop_dev_db(dev_db: &str, client: &Option<Client>) -> Result<()> {
+    // The name is validated in Settings
+    let drop_db_sql = format!("DROP DATABASE IF EXISTS {};", dev_db);
+    if let Some(c) = client {
+        log::info!("Dropping \"{}\" DB", dev_db);
+        c.execute(&drop_db_sql, &[]).await.inspect_err(|e| {
+            log::warn!("Failed to drop \"{}\" database: {}", dev_db, e);
+        })?;
+    } else {
+        println!("{}", drop_db_sql);
+    }
+    Ok(())
+}
+
+pub async fn uninstall(cli: &Cli, args: &UninstallArgs) -> Result<()> {
+    let dry_run = args.dry_run.unwrap_or(false);
+    let force = args.force.unwrap_or(false);
+
+    log::info!("Uninstalling Schemamap.io Postgres SDK");
+
+    // No reason to prompt for input if not interactive/TTY
+    let interactive = atty::is(atty::Stream::Stdout) && args.input.unwrap_or(true);
+
+    let settings = Settings::resolve(cli)?;
+    let dev_db = settings.dev_dbname.value.clone();
+
+    let mut pgconfig = initialize_pgconfig(cli, interactive);
+
+    let client = if dry_run {
+        None
+    } else {
+        let prompt = interactive && atty::is(atty::Stream::Stdin);
+        Some(porcelain::connect_with_prompt(&mut pgconfig, prompt).await?)
+    };
+
+    let dbname = pgconfig.get_dbname().unwrap_or("postgres").to_string();
+
+    init::ensure_primary(&client).await?;
+
+    let schema = settings.schema.value.clone();
+
+    log::info!(
+        "Removing Schemamap.io Postgres SDK from the {} schema of DB: {}",
+        schema,
+        dbname
+    );
+
+    if dry_run {
+        // Otherwise psql carries on after the guard fails, e.g. with dropping the dev DB
+        println!("\\set ON_ERROR_STOP on");
+    }
+    remove_schemamap(&pgconfig, &schema, &client, force).await?;
+
+    log::info!("Schemamap.io Postgres SDK uninstalled successfully");
+
+    let dev_db_exists: bool = if let Some(c) = &client {
+        c.query_one(
+            "SELECT exists(select 1 from pg_database where datname = $1)",
+            &[&dev_db],
+        )
+        .await?
+        .get::<_, bool>(0)
+    } else {
+        true
+    };
+
+    let drop_dev = if dev_db_exists && interactive && args.dev.is_none() && !dry_run {
+        prompt_for_dev_removal(&dev_db)
+    } else {
+        args.dev.unwrap_or(false)
+    };
+
+    if dev_db_exists && drop_dev {
+        drop_dev_db(&dev_db, &client).await?;
+    }
+
+    Ok(())
+}
+
+fn prompt_for_dev_removal(dev_db: &str) -> bool {
+    dialoguer::Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Do you also want to drop the \"{}\" DB with its snapshot list?",
+            dev_db
+        ))
+        .default(false)
+        .interact()
+        .unwrap_or(false)
+}
@@ -5,6 +5,8 @@ use tokio::{signal, sync::broadcast};
 use url::Url;
 use url_open::open;
 
+use crate::{common::Cli, config::Settings};
+
 #[derive(Parser, Debug, Default, Clone)]
 pub struct UpArgs {
     #[arg(
@@ -57,7 +59,7 @@ fn read_multiline_string_from_stdin() -> Result<String> {
     Ok(trimmed_input)
 }
 
-fn lookup_filepaths(extra_paths: &Vec<Option<PathBuf>>) -> Vec<PathBuf> {
+fn lookup_filepaths(extra_paths: &[Option<PathBuf>]) -> Vec<PathBuf> {
     extra_paths
         .iter()
         .chain(
@@ -72,9 +74,9 @@ fn lookup_filepaths(extra_paths: &Vec<Option<PathBuf>>) -> Vec<PathBuf> {
 }
 
 pub(crate) fn find_first_existing_tunnel_config_file(
-    extra_paths: &Vec<Option<PathBuf>>,
+    extra_paths: &[Option<PathBuf>],
 ) -> Option<PathBuf> {
-    lookup_filepaths(&extra_paths)
+    lookup_filepaths(extra_paths)
         .iter()
         .find(|p| {
             log::debug!(
@@ -86,8 +88,9 @@ pub(crate) fn find_first_existing_tunnel_config_file(
         .cloned()
 }
 
-pub(crate) async fn up(args: UpArgs) -> Result<()> {
-    let extra_paths = vec![args.file.clone()].clone();
+pub(crate) async fn up(cli: &Cli, args: &UpArgs) -> Result<()> {
+    let tunnel_config = Settings::resolve(cli)?.tunnel_config.map(|s| s.value);
+    let extra_paths = vec![args.file.clone(), tunnel_config];
     let first_existing_filepath = find_first_existing_tunnel_config_file(&extra_paths).clone();
 
     let _ =
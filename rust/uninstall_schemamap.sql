@@ -0,0 +1,63 @@
+-- Reverses create_schemamap_users.sql, create_schemamap_schema.sql and grant_schemamap_usage.sql
+begin;
+
+-- check if the current user has superuser/role creation privileges
+do $$
+begin
+  if (select rolsuper or rolcreaterole as valid_role from pg_roles where rolname = current_user) is not true then
+    raise exception 'This script must be run by a user with CREATE ROLE privileges (usually "postgres" or $(whoami)).';
+  end if;
+end $$;
+
+-- the schema is owned by schemamap, so act as it to drop it
+do $$
+begin
+    if exists (select 1 from pg_roles where rolname = 'schemamap') then
+        grant schemamap to CURRENT_USER;
+    end if;
+end $$;
+
+-- also removes the default privileges and grants on its objects given to application roles
+drop schema if exists schemamap cascade;
+
+-- revoke the usage granted on all schemas, then drop the roles from most to least permissive, keeping anything they still own
+do $$
+declare
+    rec record;
+    role_name text;
+begin
+    -- other installs in this DB, e.g. with `schemamap init --schema`, still need the roles
+    if exists (select 1 from pg_namespace n join pg_roles r on r.oid = n.nspowner where r.rolname = 'schemamap') then
+        raise notice 'Keeping the schemamap roles, they still own other SDK schemas in this DB';
+        revoke schemamap from CURRENT_USER;
+        return;
+    end if;
+
+    if exists (select 1 from pg_roles where rolname = 'schemamap_schema_read') then
+        alter default privileges revoke usage on schemas from schemamap_schema_read;
+
+        for rec in
+            select schema_name
+            from information_schema.schemata
+            where schema_name not in ('pg_catalog', 'information_schema', 'pg_toast')
+        loop
+            execute format('revoke usage on schema %I from schemamap_schema_read', rec.schema_name);
+        end loop;
+    end if;
+
+    foreach role_name in array array['schemamap', 'schemamap_readwrite', 'schemamap_readonly', 'schemamap_schema_read']
+    loop
+        if exists (select 1 from pg_roles where rolname = role_name) then
+            execute format('reassign owned by %I to %I', role_name, current_user);
+            -- revokes the remaining privileges in this DB, like CONNECT and CREATE on it
+            execute format('drop owned by %I', role_name);
+            begin
+                execute format('drop role %I', role_name);
+            exception when dependent_objects_still_exist then
+                raise warning 'Role % still has privileges in other databases, run schemamap uninstall there too: %', role_name, sqlerrm;
+            end;
+        end if;
+    end loop;
+end $$;
+
+commit;